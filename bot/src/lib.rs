@@ -0,0 +1,211 @@
+//! Headless bots of the game for load and soak testing: join rooms in the
+//! market, play in the node over WebSocket with a `PeerKey`, and measure the
+//! node throughput and latency.
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use z4_bevy::{build_request, parse_response, PeerKey, RoomId};
+
+pub mod market;
+pub mod runner;
+pub mod strategy;
+
+use runner::Metrics;
+use strategy::{Strategy, View};
+
+/// the node rejects moves faster than its timeout (300ms)
+pub const MOVE_INTERVAL: Duration = Duration::from_millis(350);
+/// the move is lost if not broadcast in time
+const MOVE_LOST: Duration = Duration::from_secs(3);
+
+/// room || sequence || position, same as the game client
+fn move_message(room: RoomId, seq: u64, x: usize, y: usize) -> Vec<u8> {
+    let mut bytes = room.to_be_bytes().to_vec();
+    bytes.extend(seq.to_be_bytes());
+    bytes.extend((x as u32).to_be_bytes());
+    bytes.extend((y as u32).to_be_bytes());
+    bytes
+}
+
+pub struct Bot {
+    // wallet account, the player id in the node messages
+    pub account: String,
+    pub peer: PeerKey,
+    pub strategy: Box<dyn Strategy>,
+    view: View,
+    seq: u64,
+    // the sent move and its time, waiting the broadcast
+    pending: Option<((usize, usize), Instant)>,
+}
+
+impl Bot {
+    pub fn new(account: String, peer: PeerKey, strategy: Box<dyn Strategy>) -> Self {
+        Self {
+            account,
+            peer,
+            strategy,
+            view: View::default(),
+            seq: 0,
+            pending: None,
+        }
+    }
+
+    /// play the room in the node until game over, or the deadline
+    pub async fn play(
+        &mut self,
+        url: &str,
+        room: RoomId,
+        deadline: Instant,
+        metrics: &Metrics,
+    ) -> Result<(), String> {
+        let (ws, _) = connect_async(url).await.map_err(|e| e.to_string())?;
+        let (mut sender, mut receiver) = ws.split();
+
+        // the first request of the z4 websocket connects the room
+        let connect = build_request("connect", vec![], &self.peer, room);
+        sender
+            .send(Message::Text(connect))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut ticker = tokio::time::interval(MOVE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if Instant::now() > deadline {
+                        return Ok(());
+                    }
+                    if let Some(request) = self.next_move(room, metrics) {
+                        sender.send(Message::Text(request)).await.map_err(|e| e.to_string())?;
+                    }
+                }
+                message = receiver.next() => {
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => return Err(e.to_string()),
+                    };
+                    metrics.received();
+
+                    if let Ok((_room, method, params)) = parse_response(&text) {
+                        if self.handle(&method, params, metrics) {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// sign the next move of the strategy
+    fn next_move(&mut self, room: RoomId, metrics: &Metrics) -> Option<String> {
+        if let Some((_, sent)) = self.pending {
+            if sent.elapsed() < MOVE_LOST {
+                return None;
+            }
+            metrics.lost();
+            self.pending = None;
+        }
+
+        let (x, y) = self.strategy.next(&self.view)?;
+        let seq = self.seq + 1;
+        let signature = self.peer.sign(&move_message(room, seq, x, y)).ok()?;
+        self.seq = seq;
+        self.pending = Some(((x, y), Instant::now()));
+        metrics.sent();
+
+        Some(build_request(
+            "move",
+            vec![
+                x.into(),
+                y.into(),
+                seq.into(),
+                hex::encode(signature.to_bytes()).into(),
+            ],
+            &self.peer,
+            room,
+        ))
+    }
+
+    /// update the view, return true when game over
+    fn handle(&mut self, method: &str, mut params: Vec<Value>, metrics: &Metrics) -> bool {
+        let position = |v: &[Value]| {
+            (
+                v[1].as_u64().unwrap_or(0) as usize,
+                v[2].as_u64().unwrap_or(0) as usize,
+            )
+        };
+
+        match method {
+            "connected" => {
+                // players status, and the board at last
+                params.pop();
+                self.view.opponents.clear();
+                for param in params {
+                    // account, position and score, the bot flag at last from pozk
+                    if let Some(status) = param.as_array().filter(|s| s.len() >= 4) {
+                        let account = status[0].as_str().unwrap_or("").to_lowercase();
+                        if account == self.account {
+                            self.view.position = position(status);
+                        } else {
+                            self.view.opponents.insert(account, position(status));
+                        }
+                    }
+                }
+            }
+            // the sequence at last from the new sequencers
+            "moved" if params.len() >= 3 => {
+                let account = params[0].as_str().unwrap_or("").to_lowercase();
+                let to = position(&params);
+                if account == self.account {
+                    self.view.position = to;
+                    if let Some((pending, sent)) = self.pending {
+                        if pending == to {
+                            metrics.acked(sent.elapsed());
+                            self.pending = None;
+                        }
+                    }
+                } else {
+                    self.view.opponents.insert(account, to);
+                }
+            }
+            "cake" if params.len() == 3 => {
+                let index = params[0].as_u64().unwrap_or(0) as u32;
+                self.view.cakes.insert(index, position(&params));
+            }
+            "eaten" if !params.is_empty() => {
+                let index = params[0].as_u64().unwrap_or(0) as u32;
+                self.view.cakes.remove(&index);
+            }
+            "over" => return true,
+            _ => {}
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use strategy::Greedy;
+
+    #[test]
+    fn connected_reads_all_status() {
+        let peer = PeerKey::generate(&mut rand::thread_rng());
+        let mut bot = Bot::new("0x01".to_owned(), peer, Box::new(Greedy));
+        let params = vec![
+            json!(("0x01", 3, 4, 0)),
+            json!(("0x02", 5, 6, 1, false)),
+            json!(("0x03", 7, 8, 0, true)),
+            json!("board"),
+        ];
+        assert!(!bot.handle("connected", params, &Metrics::default()));
+        assert_eq!(bot.view.position, (3, 4));
+        assert_eq!(bot.view.opponents.len(), 2);
+        assert_eq!(bot.view.opponents["0x03"], (7, 8));
+    }
+}
@@ -0,0 +1,35 @@
+use alien_cake_addict_bot::runner::{run, Config};
+use std::time::Duration;
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let config = Config {
+        rpc: env_or("RPC", "http://127.0.0.1:8545".to_owned()),
+        game: env_or(
+            "GAMES",
+            "0xe7f1725E7734CE288F8367e1Bb143E90bb3F0512"
+                .parse()
+                .unwrap(),
+        ),
+        websocket: std::env::var("URL_WEBSOCKET").ok(),
+        bots: env_or("BOTS", 100),
+        players: env_or("PLAYERS", 4),
+        strategy: env_or("STRATEGY", "greedy".to_owned()),
+        duration: Duration::from_secs(env_or("DURATION", 120)),
+    };
+
+    match run(config).await {
+        Ok(report) => println!("{}", report),
+        Err(err) => eprintln!("Bots failed: {}", err),
+    }
+}
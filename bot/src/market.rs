@@ -0,0 +1,181 @@
+//! Room market client of the bots. Transactions are sent by `eth_sendTransaction`
+//! without signing, so it works with the local market only.
+
+use alien_cake_addict_market::ABI;
+use ethers_core::{
+    abi::{parse_abi, Abi, RawLog, Token},
+    types::{Address, Bytes, H256},
+};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use z4_bevy::RoomId;
+
+/// the playing status of room
+const PLAYING: u64 = 3;
+
+pub struct MarketClient {
+    client: reqwest::Client,
+    url: String,
+    game: Address,
+    abi: Abi,
+}
+
+impl MarketClient {
+    pub fn new(url: &str, game: Address) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_owned(),
+            game,
+            abi: parse_abi(ABI).expect("invalid market abi"),
+        }
+    }
+
+    async fn rpc(&self, method: &str, params: Value) -> Result<Value, String> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(error) = response.get("error") {
+            return Err(error["message"].as_str().unwrap_or("rpc error").to_owned());
+        }
+        Ok(response["result"].clone())
+    }
+
+    fn encode(&self, name: &str, params: &[Token]) -> Vec<u8> {
+        self.abi
+            .functions_by_name(name)
+            .ok()
+            .and_then(|fs| fs.iter().find(|f| f.inputs.len() == params.len()))
+            .and_then(|f| f.encode_input(params).ok())
+            .unwrap_or_default()
+    }
+
+    async fn call(&self, name: &str, params: &[Token]) -> Result<Vec<Token>, String> {
+        let data = Bytes::from(self.encode(name, params));
+        let result = self
+            .rpc(
+                "eth_call",
+                json!([{ "to": self.game, "data": data }, "latest"]),
+            )
+            .await?;
+        let output = hex::decode(result.as_str().unwrap_or("").trim_start_matches("0x"))
+            .map_err(|e| e.to_string())?;
+
+        let function = self.abi.function(name).map_err(|e| e.to_string())?;
+        function.decode_output(&output).map_err(|e| e.to_string())
+    }
+
+    /// send the transaction and wait the receipt
+    async fn send(&self, from: Address, name: &str, params: &[Token]) -> Result<Value, String> {
+        let data = Bytes::from(self.encode(name, params));
+        let hash = self
+            .rpc(
+                "eth_sendTransaction",
+                json!([{ "from": from, "to": self.game, "data": data }]),
+            )
+            .await?;
+
+        loop {
+            let receipt = self.rpc("eth_getTransactionReceipt", json!([hash])).await?;
+            if receipt.is_null() {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                continue;
+            }
+            if receipt["status"] != json!("0x1") {
+                return Err(format!("{} reverted", name));
+            }
+            return Ok(receipt);
+        }
+    }
+
+    pub async fn create_room(&self, account: Address, peer: Address) -> Result<RoomId, String> {
+        let receipt = self
+            .send(
+                account,
+                "createRoom",
+                &[
+                    Token::Uint(0.into()),
+                    Token::Bool(false),
+                    Token::Address(peer),
+                    Token::FixedBytes(vec![0u8; 32]),
+                ],
+            )
+            .await?;
+
+        let event = self.abi.event("CreateRoom").map_err(|e| e.to_string())?;
+        for log in receipt["logs"].as_array().cloned().unwrap_or_default() {
+            let topics: Vec<H256> =
+                serde_json::from_value(log["topics"].clone()).unwrap_or_default();
+            let data: Bytes = serde_json::from_value(log["data"].clone()).unwrap_or_default();
+            if topics.first() != Some(&event.signature()) {
+                continue;
+            }
+            let log = event
+                .parse_log(RawLog {
+                    topics,
+                    data: data.to_vec(),
+                })
+                .map_err(|e| e.to_string())?;
+            if let Some(room) = log.params[0].value.clone().into_uint() {
+                return Ok(room.as_u64());
+            }
+        }
+        Err("no room created".to_owned())
+    }
+
+    pub async fn join_room(
+        &self,
+        account: Address,
+        room: RoomId,
+        peer: Address,
+    ) -> Result<(), String> {
+        self.send(
+            account,
+            "joinRoom",
+            &[
+                Token::Uint(room.into()),
+                Token::Address(peer),
+                Token::FixedBytes(vec![0u8; 32]),
+            ],
+        )
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn start_room(&self, account: Address, room: RoomId) -> Result<(), String> {
+        self.send(account, "startRoom", &[Token::Uint(room.into())])
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn player_limit(&self) -> Result<usize, String> {
+        let limit = self.call("playerLimit", &[]).await?;
+        Ok(limit[0].clone().into_uint().unwrap_or_default().as_usize())
+    }
+
+    /// wait the room accepted by a sequencer, return its websocket
+    pub async fn wait_playing(&self, room: RoomId, timeout: Duration) -> Result<String, String> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            let info = self.call("roomInfo", &[Token::Uint(room.into())]).await?;
+            let status = info[4].clone().into_uint().unwrap_or_default().as_u64();
+            if status == PLAYING {
+                let sequencer = info[2].clone().into_address().unwrap_or_default();
+                let info = self
+                    .call("sequencers", &[Token::Address(sequencer)])
+                    .await?;
+                return Ok(info[1].clone().into_string().unwrap_or_default());
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        Err(format!("room {} not accepted", room))
+    }
+}
@@ -0,0 +1,219 @@
+//! Run hundreds of bots in rooms, and report the node throughput and latency.
+
+use ethers_core::types::{Address, H160};
+use rand::Rng;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use z4_bevy::PeerKey;
+
+use crate::{market::MarketClient, strategy::strategy, Bot};
+
+/// Counters shared by all bots.
+#[derive(Default)]
+pub struct Metrics {
+    sent: AtomicU64,
+    acked: AtomicU64,
+    lost: AtomicU64,
+    received: AtomicU64,
+    failed: AtomicU64,
+    latencies: Mutex<Vec<Duration>>,
+}
+
+impl Metrics {
+    pub fn sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// the move is broadcast after the latency
+    pub fn acked(&self, latency: Duration) {
+        self.acked.fetch_add(1, Ordering::Relaxed);
+        self.latencies.lock().unwrap().push(latency);
+    }
+
+    pub fn lost(&self) {
+        self.lost.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn report(&self, bots: usize, elapsed: Duration) -> Report {
+        let mut latencies = self.latencies.lock().unwrap().clone();
+        latencies.sort();
+        let percentile = |p: usize| {
+            if latencies.is_empty() {
+                Duration::ZERO
+            } else {
+                latencies[(latencies.len() - 1) * p / 100]
+            }
+        };
+
+        Report {
+            bots,
+            elapsed,
+            sent: self.sent.load(Ordering::Relaxed),
+            acked: self.acked.load(Ordering::Relaxed),
+            lost: self.lost.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+pub struct Report {
+    pub bots: usize,
+    pub elapsed: Duration,
+    // moves sent, broadcast back, and lost
+    pub sent: u64,
+    pub acked: u64,
+    pub lost: u64,
+    // messages received by all bots
+    pub received: u64,
+    // bots failed to join or play
+    pub failed: u64,
+    // move latency percentiles
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64().max(0.001);
+        writeln!(
+            f,
+            "bots: {}, failed: {}, elapsed: {:.1}s",
+            self.bots, self.failed, secs
+        )?;
+        writeln!(
+            f,
+            "moves: {} sent, {} acked, {} lost, {:.1} acked/s",
+            self.sent,
+            self.acked,
+            self.lost,
+            self.acked as f64 / secs
+        )?;
+        writeln!(
+            f,
+            "messages: {} received, {:.1}/s",
+            self.received,
+            self.received as f64 / secs
+        )?;
+        write!(
+            f,
+            "latency: p50 {:?}, p95 {:?}, p99 {:?}, max {:?}",
+            self.p50, self.p95, self.p99, self.max
+        )
+    }
+}
+
+pub struct Config {
+    // market JSON-RPC
+    pub rpc: String,
+    // game contract
+    pub game: Address,
+    // node websocket, the sequencer registered websocket if none
+    pub websocket: Option<String>,
+    pub bots: usize,
+    // players in every room
+    pub players: usize,
+    // `random` or `greedy`
+    pub strategy: String,
+    pub duration: Duration,
+}
+
+/// Create rooms for all bots, play them at the same time, and report.
+pub async fn run(config: Config) -> Result<Report, String> {
+    if strategy(&config.strategy, 0).is_none() {
+        return Err(format!("unknown strategy {}", config.strategy));
+    }
+    if config.players == 0 {
+        return Err("no players in room".to_owned());
+    }
+
+    let config = Arc::new(config);
+    let metrics = Arc::new(Metrics::default());
+    let start = Instant::now();
+
+    let mut rooms = vec![];
+    for group in 0..config.bots.div_ceil(config.players) {
+        let players = config.players.min(config.bots - group * config.players);
+        let (config, metrics) = (config.clone(), metrics.clone());
+        rooms.push(tokio::spawn(async move {
+            if let Err(err) = run_room(&config, players, &metrics).await {
+                tracing::warn!("room of bots failed: {}", err);
+                for _ in 0..players {
+                    metrics.failed();
+                }
+            }
+        }));
+    }
+    for room in rooms {
+        let _ = room.await;
+    }
+
+    Ok(metrics.report(config.bots, start.elapsed()))
+}
+
+async fn run_room(config: &Config, players: usize, metrics: &Arc<Metrics>) -> Result<(), String> {
+    let market = MarketClient::new(&config.rpc, config.game);
+
+    let bots: Vec<(Address, Bot)> = {
+        let mut rng = rand::thread_rng();
+        (0..players)
+            .map(|_| {
+                let account = Address::from(rng.gen::<[u8; 20]>());
+                let peer = PeerKey::generate(&mut rng);
+                let strategy = strategy(&config.strategy, rng.gen()).expect("checked strategy");
+                (account, Bot::new(format!("{:?}", account), peer, strategy))
+            })
+            .collect()
+    };
+
+    // the first bot creates the room, the others join, full room starts
+    let accounts: Vec<(Address, Address)> = bots
+        .iter()
+        .map(|(account, bot)| (*account, H160(bot.peer.peer_id().0)))
+        .collect();
+    let room = market.create_room(accounts[0].0, accounts[0].1).await?;
+    for (account, peer) in accounts.iter().skip(1) {
+        market.join_room(*account, room, *peer).await?;
+    }
+    if players < market.player_limit().await? {
+        market.start_room(accounts[0].0, room).await?;
+    }
+    let websocket = market.wait_playing(room, Duration::from_secs(60)).await?;
+    let url = config.websocket.clone().unwrap_or(websocket);
+
+    let deadline = Instant::now() + config.duration;
+    let mut tasks = vec![];
+    for (_, mut bot) in bots {
+        let (url, metrics) = (url.clone(), metrics.clone());
+        tasks.push(tokio::spawn(async move {
+            if let Err(err) = bot.play(&url, room, deadline, &metrics).await {
+                tracing::warn!("bot {} failed: {}", bot.account, err);
+                metrics.failed();
+            }
+        }));
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! Bot strategies, every call returns the next cell of one step.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{HashMap, VecDeque};
+
+pub const BOARD_SIZE_I: usize = 14;
+pub const BOARD_SIZE_J: usize = 21;
+
+/// What the bot knows about the game.
+#[derive(Clone, Debug, Default)]
+pub struct View {
+    pub position: (usize, usize),
+    // other players by account
+    pub opponents: HashMap<String, (usize, usize)>,
+    // alive cakes by index
+    pub cakes: HashMap<u32, (usize, usize)>,
+}
+
+pub trait Strategy: Send {
+    /// next position, none to stay
+    fn next(&mut self, view: &View) -> Option<(usize, usize)>;
+}
+
+/// the strategy by name: `random` or `greedy`
+pub fn strategy(name: &str, seed: u64) -> Option<Box<dyn Strategy>> {
+    match name {
+        "random" => Some(Box::new(RandomWalk::new(seed))),
+        "greedy" => Some(Box::new(Greedy)),
+        _ => None,
+    }
+}
+
+fn neighbors((i, j): (usize, usize)) -> Vec<(usize, usize)> {
+    let mut cells = vec![];
+    if i > 0 {
+        cells.push((i - 1, j));
+    }
+    if i < BOARD_SIZE_I - 1 {
+        cells.push((i + 1, j));
+    }
+    if j > 0 {
+        cells.push((i, j - 1));
+    }
+    if j < BOARD_SIZE_J - 1 {
+        cells.push((i, j + 1));
+    }
+    cells
+}
+
+/// Step to a random neighbor cell.
+pub struct RandomWalk {
+    rng: StdRng,
+}
+
+impl RandomWalk {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomWalk {
+    fn next(&mut self, view: &View) -> Option<(usize, usize)> {
+        let cells = neighbors(view.position);
+        Some(cells[self.rng.gen_range(0..cells.len())])
+    }
+}
+
+/// Step to the nearest cake by BFS, the cells of opponents are blocked.
+pub struct Greedy;
+
+impl Strategy for Greedy {
+    fn next(&mut self, view: &View) -> Option<(usize, usize)> {
+        if view.cakes.is_empty() {
+            return None;
+        }
+
+        let mut blocked = [[false; BOARD_SIZE_J]; BOARD_SIZE_I];
+        for (i, j) in view.opponents.values() {
+            blocked[*i][*j] = true;
+        }
+        let mut targets = [[false; BOARD_SIZE_J]; BOARD_SIZE_I];
+        for (i, j) in view.cakes.values() {
+            targets[*i][*j] = true;
+        }
+
+        // first step of the path to every visited cell
+        let mut first: [[Option<(usize, usize)>; BOARD_SIZE_J]; BOARD_SIZE_I] =
+            [[None; BOARD_SIZE_J]; BOARD_SIZE_I];
+        let mut queue = VecDeque::new();
+        queue.push_back(view.position);
+        blocked[view.position.0][view.position.1] = true;
+
+        while let Some(cell) = queue.pop_front() {
+            for next in neighbors(cell) {
+                // cakes under opponents are still reachable
+                if blocked[next.0][next.1] && !targets[next.0][next.1] {
+                    continue;
+                }
+                if first[next.0][next.1].is_some() || next == view.position {
+                    continue;
+                }
+
+                let step = first[cell.0][cell.1].unwrap_or(next);
+                if targets[next.0][next.1] {
+                    return Some(step);
+                }
+                first[next.0][next.1] = Some(step);
+                queue.push_back(next);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greedy_goes_to_nearest_cake() {
+        let mut view = View {
+            position: (5, 5),
+            ..Default::default()
+        };
+        assert_eq!(Greedy.next(&view), None);
+
+        view.cakes.insert(1, (5, 9));
+        view.cakes.insert(2, (2, 5));
+        assert_eq!(Greedy.next(&view), Some((4, 5)));
+
+        // go around the opponent
+        view.opponents.insert("0x01".to_owned(), (4, 5));
+        let next = Greedy.next(&view).unwrap();
+        assert!(next == (5, 4) || next == (5, 6));
+
+        // stay in the board
+        let view = View {
+            position: (0, 0),
+            ..Default::default()
+        };
+        let mut random = RandomWalk::new(1);
+        for _ in 0..100 {
+            let (i, j) = random.next(&view).unwrap();
+            assert!(i + j == 1);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! Circuit specific setup of `MAX_OPERATIONS`, write the keys to the artifact.
+//!
+//! `cargo run --release --bin setup -- circuit.keys`
+
+use alien_cake_addict_circuit::{Prover, MAX_OPERATIONS};
+use rand::rngs::OsRng;
+
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or("circuit.keys".to_owned());
+
+    // the randomness is dropped with the OsRng after setup
+    let (prover, _) = Prover::setup(MAX_OPERATIONS, &mut OsRng).expect("setup failed");
+    let bytes = prover.to_bytes().expect("invalid keys");
+    std::fs::write(&path, bytes).expect("write keys failed");
+    println!("keys of {} operations: {}", MAX_OPERATIONS, path);
+}
@@ -1,14 +1,554 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
+//! Groth16 circuit of the game scoring, an alternative to the risc0 guest
+//! with small proofs and without a zkVM.
+//!
+//! The circuit replays the operations on the board and proves the scores of
+//! the players. Operations are committed with a poseidon hash chain, keccak is
+//! too expensive in the circuit. Move signatures and sequences are not in the
+//! circuit, they are checked before the operations are accepted.
+//!
+//! The keys are from the setup artifact written by `cargo run --bin setup`,
+//! the statement binds the players and the keccak of the game result, and the
+//! verifier checks the result has the proven room, players, scores and rank.
+
+use aca_core::{result_types, standings, Operation, BOARD_SIZE_I, BOARD_SIZE_J};
+use ark_bn254::{Bn254, Fr};
+use ark_crypto_primitives::sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{
+        constraints::PoseidonSpongeVar, find_poseidon_ark_and_mds, PoseidonConfig, PoseidonSponge,
+    },
+    CryptographicSponge,
+};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, ProvingKey};
+use ark_r1cs_std::{
+    alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::fp::FpVar, prelude::*,
+};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use ark_std::rand::{CryptoRng, RngCore};
+use ethers_core::{
+    abi::{decode, Token},
+    types::{Address, U256},
+    utils::keccak256,
+};
+use std::{fs, path::Path};
+
+pub const MAX_PLAYERS: usize = 4;
+pub const MAX_CAKES: usize = 16;
+pub const MAX_OPERATIONS: usize = 2048;
+
+const PAD: usize = 0;
+const MOVE: usize = 1;
+const CAKE_CREATED: usize = 2;
+const CAKE_MISSED: usize = 3;
+
+/// The operation as circuit witness, unused fields are zero.
+#[derive(Clone, Copy, Default, Debug)]
+struct Step {
+    kind: usize,
+    player: usize,
+    x: usize,
+    y: usize,
+    index: usize,
+}
+
+impl Step {
+    fn new(players: &[Address], op: &Operation) -> Result<Self, &'static str> {
+        let step = match op {
+            Operation::Move(player, x, y, _seq, _signature) => Step {
+                kind: MOVE,
+                player: players
+                    .iter()
+                    .position(|p| p == player)
+                    .ok_or("move from unknown player")?,
+                x: *x,
+                y: *y,
+                index: 0,
+            },
+            Operation::CakeCreated(index, x, y) => Step {
+                kind: CAKE_CREATED,
+                player: 0,
+                x: *x,
+                y: *y,
+                index: *index as usize,
+            },
+            Operation::CakeMissed(index) => Step {
+                kind: CAKE_MISSED,
+                player: 0,
+                x: 0,
+                y: 0,
+                index: *index as usize,
+            },
+        };
+        if step.x >= BOARD_SIZE_I || step.y >= BOARD_SIZE_J {
+            return Err("position out of board");
+        }
+        if step.index >= MAX_CAKES {
+            return Err("cake index out of circuit");
+        }
+        Ok(step)
+    }
+
+    fn cell(&self) -> usize {
+        self.x * BOARD_SIZE_J + self.y
+    }
+
+    /// kind || player || cell || index, the element of the hash chain
+    fn element(&self) -> Fr {
+        Fr::from((self.kind + 4 * self.player + 16 * self.cell() + 8192 * self.index) as u64)
+    }
+}
+
+/// The public statement of the proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub room: u64,
+    // players, zero for the empty seats
+    pub players: Vec<Address>,
+    // scores, same order as players, zero for the empty seats
+    pub scores: Vec<u32>,
+    // poseidon hash chain head of the operations, big-endian
+    pub head: [u8; 32],
+    // keccak256 of the game result, see aca_core `game_result`
+    pub result: [u8; 32],
+}
+
+impl Statement {
+    /// room, players, scores, head, and the result as two 128-bit halves
+    fn public_inputs(&self) -> Vec<Fr> {
+        let mut inputs = vec![Fr::from(self.room)];
+        inputs.extend(
+            self.players
+                .iter()
+                .map(|p| Fr::from_be_bytes_mod_order(p.as_bytes())),
+        );
+        inputs.extend(self.scores.iter().map(|s| Fr::from(*s as u64)));
+        inputs.push(Fr::from_be_bytes_mod_order(&self.head));
+        inputs.push(Fr::from_be_bytes_mod_order(&self.result[..16]));
+        inputs.push(Fr::from_be_bytes_mod_order(&self.result[16..]));
+        inputs
+    }
+}
+
+/// players padded with zero to the circuit seats
+fn seats(players: &[Address]) -> Vec<Address> {
+    let mut seats = players.to_vec();
+    seats.resize(MAX_PLAYERS, Address::zero());
+    seats
+}
+
+fn poseidon_config() -> PoseidonConfig<Fr> {
+    // rate 2, alpha 5, 8 full rounds and 57 partial rounds
+    let (ark, mds) = find_poseidon_ark_and_mds::<Fr>(Fr::MODULUS_BIT_SIZE as u64, 2, 8, 57, 0);
+    PoseidonConfig::new(8, 57, 5, mds, ark, 2, 1)
+}
+
+fn poseidon(config: &PoseidonConfig<Fr>, a: Fr, b: Fr) -> Fr {
+    let mut sponge = PoseidonSponge::new(config);
+    sponge.absorb(&vec![a, b]);
+    sponge.squeeze_field_elements(1)[0]
+}
+
+/// Replay the operations natively, the statement the circuit proves.
+pub fn statement(
+    room: u64,
+    players: &[Address],
+    operations: &[Operation],
+    result: &[u8],
+) -> Result<Statement, &'static str> {
+    let steps = steps(players, operations)?;
+    let (scores, head) = replay(room, &steps);
+    Ok(Statement {
+        room,
+        players: seats(players),
+        scores,
+        head: to_bytes(head),
+        result: keccak256(result),
+    })
+}
+
+fn steps(players: &[Address], operations: &[Operation]) -> Result<Vec<Step>, &'static str> {
+    if players.len() > MAX_PLAYERS {
+        return Err("too many players");
+    }
+    operations.iter().map(|op| Step::new(players, op)).collect()
+}
+
+fn replay(room: u64, steps: &[Step]) -> (Vec<u32>, Fr) {
+    let config = poseidon_config();
+    let mut head = Fr::from(room);
+    let mut scores = vec![0u32; MAX_PLAYERS];
+    // cell of every alive cake, by cake index
+    let mut cakes: Vec<Option<usize>> = vec![None; MAX_CAKES];
+
+    for step in steps {
+        match step.kind {
+            MOVE => {
+                for cake in cakes.iter_mut() {
+                    if *cake == Some(step.cell()) {
+                        *cake = None;
+                        scores[step.player] += 1;
+                    }
+                }
+            }
+            CAKE_CREATED => cakes[step.index] = Some(step.cell()),
+            CAKE_MISSED => cakes[step.index] = None,
+            _ => continue,
+        }
+        head = poseidon(&config, head, step.element());
+    }
+
+    (scores, head)
+}
+
+fn to_bytes(f: Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&f.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// The scoring circuit of `capacity` operations, the rest are padding.
+#[derive(Clone)]
+struct ScoreCircuit {
+    statement: Statement,
+    steps: Vec<Step>,
+}
+
+impl ScoreCircuit {
+    fn empty(capacity: usize) -> Self {
+        Self {
+            statement: Statement {
+                room: 0,
+                players: vec![Address::zero(); MAX_PLAYERS],
+                scores: vec![0; MAX_PLAYERS],
+                head: [0u8; 32],
+                result: [0u8; 32],
+            },
+            steps: vec![Step::default(); capacity],
+        }
+    }
+}
+
+/// allocate the one-hot booleans of value in 0..n
+fn one_hot(
+    cs: ConstraintSystemRef<Fr>,
+    value: usize,
+    n: usize,
+) -> Result<(Vec<Boolean<Fr>>, FpVar<Fr>), SynthesisError> {
+    let mut bits = vec![];
+    let mut sum = FpVar::zero();
+    let mut var = FpVar::zero();
+    for i in 0..n {
+        let bit = Boolean::new_witness(cs.clone(), || Ok(i == value))?;
+        sum += FpVar::from(bit.clone());
+        var += FpVar::from(bit.clone()) * Fr::from(i as u64);
+        bits.push(bit);
+    }
+    sum.enforce_equal(&FpVar::one())?;
+    Ok((bits, var))
+}
+
+impl ConstraintSynthesizer<Fr> for ScoreCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let inputs = self.statement.public_inputs();
+        let room = FpVar::new_input(cs.clone(), || Ok(inputs[0]))?;
+        // players and result are only bound to the proof, an input in no
+        // constraint could be changed without breaking the proof
+        for player in &inputs[1..=MAX_PLAYERS] {
+            let _ = FpVar::new_input(cs.clone(), || Ok(*player))?.square()?;
+        }
+        let mut public_scores = vec![];
+        for score in &inputs[MAX_PLAYERS + 1..=2 * MAX_PLAYERS] {
+            public_scores.push(FpVar::new_input(cs.clone(), || Ok(*score))?);
+        }
+        let public_head = FpVar::new_input(cs.clone(), || Ok(inputs[2 * MAX_PLAYERS + 1]))?;
+        for half in &inputs[2 * MAX_PLAYERS + 2..] {
+            let _ = FpVar::new_input(cs.clone(), || Ok(*half))?.square()?;
+        }
+
+        let config = poseidon_config();
+        let mut head = room;
+        let mut scores = vec![FpVar::<Fr>::zero(); MAX_PLAYERS];
+        let mut alive = vec![Boolean::FALSE; MAX_CAKES];
+        let mut cells = vec![FpVar::<Fr>::zero(); MAX_CAKES];
+
+        for step in &self.steps {
+            let (kind, kind_var) = one_hot(cs.clone(), step.kind, 4)?;
+            let (player, player_var) = one_hot(cs.clone(), step.player, MAX_PLAYERS)?;
+            let (slot, index_var) = one_hot(cs.clone(), step.index, MAX_CAKES)?;
+            // one-hot positions are always in the board
+            let (_, x) = one_hot(cs.clone(), step.x, BOARD_SIZE_I)?;
+            let (_, y) = one_hot(cs.clone(), step.y, BOARD_SIZE_J)?;
+            let cell = x * Fr::from(BOARD_SIZE_J as u64) + y;
+
+            let mut eaten = FpVar::<Fr>::zero();
+            for k in 0..MAX_CAKES {
+                let same = cells[k].is_eq(&cell)?;
+                let eat = kind[MOVE].and(&alive[k])?.and(&same)?;
+                let create = kind[CAKE_CREATED].and(&slot[k])?;
+                let miss = kind[CAKE_MISSED].and(&slot[k])?;
+
+                let keep = alive[k].and(&miss.not())?.and(&eat.not())?;
+                alive[k] = create.or(&keep)?;
+                cells[k] = create.select(&cell, &cells[k])?;
+                eaten += FpVar::from(eat);
+            }
+            for p in 0..MAX_PLAYERS {
+                scores[p] += FpVar::from(player[p].clone()) * &eaten;
+            }
+
+            let element = kind_var
+                + player_var * Fr::from(4u64)
+                + &cell * Fr::from(16u64)
+                + index_var * Fr::from(8192u64);
+            let mut sponge = PoseidonSpongeVar::new(cs.clone(), &config);
+            sponge.absorb(&vec![head.clone(), element])?;
+            let next = sponge.squeeze_field_elements(1)?.remove(0);
+            head = kind[PAD].select(&head, &next)?;
+        }
+
+        for (score, public) in scores.iter().zip(public_scores.iter()) {
+            score.enforce_equal(public)?;
+        }
+        head.enforce_equal(&public_head)
+    }
+}
+
+/// Prover of games up to `capacity` operations.
+pub struct Prover {
+    capacity: usize,
+    pk: ProvingKey<Bn254>,
+}
+
+/// Verifier of the game proofs.
+pub struct Verifier {
+    pvk: PreparedVerifyingKey<Bn254>,
+}
+
+impl Prover {
+    /// circuit specific setup, the rng must be discarded after setup
+    pub fn setup<R: RngCore + CryptoRng>(
+        capacity: usize,
+        rng: &mut R,
+    ) -> Result<(Prover, Verifier), &'static str> {
+        let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(ScoreCircuit::empty(capacity), rng)
+            .map_err(|_| "setup failed")?;
+        let pvk = Groth16::<Bn254>::process_vk(&vk).map_err(|_| "setup failed")?;
+        Ok((Prover { capacity, pk }, Verifier { pvk }))
+    }
+
+    /// prove the scores of the game with its result, return the statement and the compressed proof
+    pub fn prove<R: RngCore + CryptoRng>(
+        &self,
+        room: u64,
+        players: &[Address],
+        operations: &[Operation],
+        result: &[u8],
+        rng: &mut R,
+    ) -> Result<(Statement, Vec<u8>), &'static str> {
+        if operations.len() > self.capacity {
+            return Err("too many operations");
+        }
+        let mut steps = steps(players, operations)?;
+        let (scores, head) = replay(room, &steps);
+        steps.resize(self.capacity, Step::default());
+
+        let statement = Statement {
+            room,
+            players: seats(players),
+            scores,
+            head: to_bytes(head),
+            result: keccak256(result),
+        };
+        let circuit = ScoreCircuit {
+            statement: statement.clone(),
+            steps,
+        };
+        let proof = Groth16::<Bn254>::prove(&self.pk, circuit, rng).map_err(|_| "prove failed")?;
+
+        let mut bytes = vec![];
+        proof
+            .serialize_compressed(&mut bytes)
+            .map_err(|_| "invalid proof")?;
+        Ok((statement, bytes))
+    }
+
+    /// capacity || compressed proving key, the setup artifact
+    pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
+        let mut bytes = (self.capacity as u64).to_be_bytes().to_vec();
+        self.pk
+            .serialize_compressed(&mut bytes)
+            .map_err(|_| "invalid proving key")?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        let capacity = bytes.get(..8).ok_or("invalid keys")?;
+        let capacity = u64::from_be_bytes(capacity.try_into().unwrap()) as usize;
+        let pk = ProvingKey::<Bn254>::deserialize_compressed(&bytes[8..])
+            .map_err(|_| "invalid proving key")?;
+        Ok(Prover { capacity, pk })
+    }
+
+    /// the verifier of the proving key
+    pub fn verifier(&self) -> Verifier {
+        Verifier {
+            pvk: Groth16::<Bn254>::process_vk(&self.pk.vk).expect("invalid verifying key"),
+        }
+    }
+}
+
+impl Verifier {
+    /// verify the proof of the statement, and the game result of it
+    pub fn verify(
+        &self,
+        statement: &Statement,
+        result: &[u8],
+        proof: &[u8],
+    ) -> Result<bool, &'static str> {
+        if statement.players.len() != MAX_PLAYERS || statement.scores.len() != MAX_PLAYERS {
+            return Err("invalid statement");
+        }
+        if !result_of(statement, result) {
+            return Ok(false);
+        }
+        let proof = Proof::<Bn254>::deserialize_compressed(proof).map_err(|_| "invalid proof")?;
+        Groth16::<Bn254>::verify_with_processed_vk(&self.pvk, &statement.public_inputs(), &proof)
+            .map_err(|_| "invalid proof")
+    }
+}
+
+/// the result is of the statement: keccak, room, players and scores, and the rank
+/// is the ranked standings of the scores
+fn result_of(statement: &Statement, result: &[u8]) -> bool {
+    if keccak256(result) != statement.result {
+        return false;
+    }
+    let Ok(tokens) = decode(&result_types(), result) else {
+        return false;
+    };
+    let addresses = |token: &Token| -> Vec<Address> {
+        let tokens = token.clone().into_array().unwrap_or_default();
+        tokens.into_iter().filter_map(|t| t.into_address()).collect()
+    };
+    let players = addresses(&tokens[1]);
+    let scores: Vec<u32> = tokens[3]
+        .clone()
+        .into_array()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|t| t.into_uint())
+        .filter(|s| *s <= U256::from(u32::MAX))
+        .map(|s| s.as_u32())
+        .collect();
+    if players.len() > MAX_PLAYERS || scores.len() != players.len() {
+        return false;
+    }
+
+    let rank: Vec<Address> = standings(&players, &scores)
+        .into_iter()
+        .filter(|(rank, _, _)| *rank > 0)
+        .map(|(_, player, _)| player)
+        .collect();
+    let mut padded = scores.clone();
+    padded.resize(MAX_PLAYERS, 0);
+    tokens[0].clone().into_uint() == Some(statement.room.into())
+        && seats(&players) == statement.players
+        && padded == statement.scores
+        && addresses(&tokens[4]) == rank
+}
+
+/// Load the keys from the setup artifact.
+pub fn load_keys(path: impl AsRef<Path>) -> Result<(Prover, Verifier), &'static str> {
+    let bytes = fs::read(path).map_err(|_| "missing keys")?;
+    let prover = Prover::from_bytes(&bytes)?;
+    let verifier = prover.verifier();
+    Ok((prover, verifier))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aca_core::game_result;
+    use ark_std::rand::SeedableRng;
+    use ethers_core::abi::encode;
+    use rand_chacha::ChaChaRng;
+
+    /// keys from a public seed, only for tests
+    fn dev_keys(capacity: usize) -> (Prover, Verifier) {
+        let mut rng = ChaChaRng::from_seed([0u8; 32]);
+        Prover::setup(capacity, &mut rng).expect("setup failed")
+    }
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn circuit_works() {
+        let players = vec![Address::repeat_byte(1), Address::repeat_byte(2)];
+        let operations = vec![
+            Operation::CakeCreated(1, 3, 4),
+            Operation::CakeCreated(2, 13, 20),
+            Operation::Move(players[1], 3, 4, 1, vec![]),
+            Operation::CakeCreated(3, 3, 4),
+            Operation::CakeMissed(2),
+            Operation::Move(players[0], 13, 20, 1, vec![]),
+            Operation::Move(players[0], 3, 4, 2, vec![]),
+            Operation::CakeCreated(4, 5, 5),
+            Operation::Move(players[1], 5, 5, 2, vec![]),
+        ];
+
+        let result = game_result(7, &players, &players, &[1, 2], [0u8; 32], [0u8; 32]);
+
+        let (prover, verifier) = dev_keys(10);
+        let mut rng = ChaChaRng::from_seed([1u8; 32]);
+        let (statement, proof) = prover
+            .prove(7, &players, &operations, &result, &mut rng)
+            .unwrap();
+        assert_eq!(statement.scores, vec![1, 2, 0, 0]);
+        assert_eq!(
+            statement,
+            super::statement(7, &players, &operations, &result).unwrap()
+        );
+        assert!(verifier.verify(&statement, &result, &proof).unwrap());
+
+        let mut forged = statement.clone();
+        forged.scores = vec![2, 2, 0, 0];
+        assert!(!verifier.verify(&forged, &result, &proof).unwrap());
+
+        let mut swapped = statement.clone();
+        swapped.players.swap(0, 1);
+        assert!(!verifier.verify(&swapped, &result, &proof).unwrap());
+
+        let mut rewritten = statement.clone();
+        rewritten.result = keccak256(b"other");
+        assert!(!verifier.verify(&rewritten, &result, &proof).unwrap());
+
+        // a valid proof of the scores cannot ship with other rank or players
+        let mut tokens = decode(&result_types(), &result).unwrap();
+        tokens[4] = Token::Array(vec![Token::Address(players[0]), Token::Address(players[1])]);
+        let reranked = encode(&tokens);
+        let (statement2, proof2) = prover
+            .prove(7, &players, &operations, &reranked, &mut rng)
+            .unwrap();
+        assert!(!verifier.verify(&statement2, &reranked, &proof2).unwrap());
+
+        let mut tokens = decode(&result_types(), &result).unwrap();
+        tokens[1] = Token::Array(vec![Token::Address(players[1]), Token::Address(players[0])]);
+        let swapped = encode(&tokens);
+        let (statement2, proof2) = prover
+            .prove(7, &players, &operations, &swapped, &mut rng)
+            .unwrap();
+        assert!(!verifier.verify(&statement2, &swapped, &proof2).unwrap());
+        assert!(!verifier.verify(&statement, &swapped, &proof).unwrap());
+
+        let mut other = statement.clone();
+        other.head = super::statement(7, &players, &operations[..6], &result)
+            .unwrap()
+            .head;
+        assert!(!verifier.verify(&other, &result, &proof).unwrap());
+
+        // the setup artifact has the same keys
+        let loaded = Prover::from_bytes(&prover.to_bytes().unwrap()).unwrap();
+        assert!(loaded.verifier().verify(&statement, &result, &proof).unwrap());
     }
 }
@@ -63,6 +63,7 @@ abstract contract RoomMarket is Ownable {
     event StakeSequencer(address sequencer, string http, string websocket, uint256 staking);
     event UnstakeSequencer(address sequencer, uint256 staking);
     event CreateRoom(uint256 room, address game, uint256 reward, bool viewable, address player, address peer, bytes32 pk);
+    event RoomParams(uint256 room, uint256 limit, bytes params);
     event JoinRoom(uint256 room, address player, address peer, bytes32 pk);
     event StartRoom(uint256 room, address game);
     event AcceptRoom(uint256 room, address sequencer, string websocket, uint256 locked, bytes params);
@@ -73,6 +74,7 @@ abstract contract RoomMarket is Ownable {
         token = _token;
         minStaking = _minStaking;
         playerRoomLock = _playerRoomLock;
+        require(_playerLimit > 0, "RM09");
         playerLimit = _playerLimit;
         nextRoomId = _startRoomId;
     }
@@ -90,6 +92,7 @@ abstract contract RoomMarket is Ownable {
     }
 
     function setPlayerLimit(uint256 _playerLimit) external onlyOwner {
+        require(_playerLimit > 0, "RM09");
         playerLimit = _playerLimit;
     }
 
@@ -119,6 +122,19 @@ abstract contract RoomMarket is Ownable {
     }
 
     function createRoom(uint256 ticket, bool viewable, address peer, bytes32 pk) external returns (uint256) {
+        return _createRoom(ticket, viewable, peer, pk, playerLimit);
+    }
+
+    /// create room with less players than playerLimit, and the game params
+    function createRoomWithParams(uint256 ticket, bool viewable, address peer, bytes32 pk, uint256 limit, bytes calldata params) external returns (uint256) {
+        require(limit > 0 && limit <= playerLimit, "RM09");
+
+        uint256 roomId = _createRoom(ticket, viewable, peer, pk, limit);
+        emit RoomParams(roomId, limit, params);
+        return roomId;
+    }
+
+    function _createRoom(uint256 ticket, bool viewable, address peer, bytes32 pk, uint256 limit) private returns (uint256) {
         // TODO Transfer ticket to contract
 
         Room storage room = rooms[nextRoomId];
@@ -130,7 +146,7 @@ abstract contract RoomMarket is Ownable {
         room.viewable = viewable;
         room.ticket = ticket;
         room.reward = ticket;
-        room.site = playerLimit - 1;
+        room.site = limit - 1;
         room.status = RoomStatus.Opening;
 
         nextRoomId += 1;
@@ -206,13 +222,22 @@ abstract contract RoomMarket is Ownable {
         require(room.status == RoomStatus.Playing, "RM02");
         require(room.sequencer == msg.sender, "RM05");
 
-        // verify sign
-        bytes32 hash = keccak256(abi.encodePacked('\x19Ethereum Signed Message:\n32', roomId, data));
-        // address signer = ECDSA.recover(hash, proof);
+        // verify sign, proof is operations head || signature (r, s, v)
+        require(proof.length == 97, "RM07");
+        bytes32 head = bytes32(proof[0:32]);
+        bytes32 message = keccak256(abi.encodePacked(roomId, keccak256(data), head));
+        bytes32 hash = keccak256(abi.encodePacked("\x19Ethereum Signed Message:\n32", message));
+        require(_recover(hash, proof[32:97]) == room.sequencer, "RM08");
 
         room.result = data;
         _overRoom(roomId);
+    }
 
+    function _recover(bytes32 hash, bytes calldata signature) private pure returns (address) {
+        bytes32 r = bytes32(signature[0:32]);
+        bytes32 s = bytes32(signature[32:64]);
+        uint8 v = uint8(signature[64]);
+        return ecrecover(hash, v, r, s);
     }
 
     function _overRoom(uint256 roomId) private {
@@ -23,14 +23,25 @@ contract SimpleGame is RoomMarket {
         Room storage room = rooms[roomId];
         require(room.status == RoomStatus.Over, "SG01");
 
-        (address[] memory winners) = abi.decode(room.result, (address[]));
+        // room, players, peers, scores, rank, operations head, seed commitment
+        // the rank only has players who scored, see aca_core game_result
+        (uint256 resultRoom, address[] memory players, address[] memory peers, , address[] memory winners, , ) = abi.decode(
+            room.result, (uint256, address[], address[], uint256[], address[], bytes32, bytes32)
+        );
+        require(resultRoom == roomId, "SG02");
+
+        // the moves are signed by the peers of the room players, bots are not in the room
+        require(players.length == peers.length, "SG03");
+        for (uint256 i = 0; i < players.length; i++) {
+            require(_isBot(players[i]) || (peers[i] != address(0) && _peerOf(room, players[i]) == peers[i]), "SG03");
+        }
         if (winners.length > 0) {
             uint256 amount = room.reward - room.ticket;
 
             // TODO transfer limit-1/limit reward to winner
 
-            ranking[winners[1]].win += 3;
-            ranking[winners[1]].reward += amount;
+            ranking[winners[0]].win += 3;
+            ranking[winners[0]].reward += amount;
             emit Ranking(winners[0], 3, amount);
         }
 
@@ -38,7 +49,7 @@ contract SimpleGame is RoomMarket {
             // TODO transfer ticket reward to winner
             uint256 amount = room.ticket;
 
-            ranking[winners[1]].win += 3;
+            ranking[winners[1]].win += 1;
             ranking[winners[1]].reward += amount;
             emit Ranking(winners[1], 1, amount);
         }
@@ -46,4 +57,18 @@ contract SimpleGame is RoomMarket {
         delete rooms[roomId];
         emit ClaimRoom(roomId);
     }
+
+    /// the reserved bot accounts, see aca_core bot_account
+    function _isBot(address player) private pure returns (bool) {
+        return uint160(player) > 0 && uint160(player) < 256;
+    }
+
+    function _peerOf(Room storage room, address player) private view returns (address) {
+        for (uint256 i = 0; i < room.players.length; i++) {
+            if (room.players[i] == player) {
+                return room.peers[i];
+            }
+        }
+        return address(0);
+    }
 }
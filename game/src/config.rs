@@ -0,0 +1,146 @@
+//! Client config with named network profiles. Loaded from the URL query in
+//! web, and from the config file and env in native, e.g.
+//! `?network=localhost&contract=0x...` or `ACA_NETWORK=localhost`.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Network profile: the chain, game contract and room market.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Network {
+    pub name: String,
+    pub chain_id: u64,
+    // chain name and native currency symbol, for adding the chain to wallet
+    #[serde(default)]
+    pub chain_name: String,
+    #[serde(default)]
+    pub currency: String,
+    // game contract address
+    pub contract: String,
+    // room market RPC
+    pub market: String,
+    // chain RPC, used by the local wallet
+    pub rpc: String,
+    // leaderboard API, none when empty
+    #[serde(default)]
+    pub leaderboard: String,
+}
+
+/// the builtin profiles, the first is default
+pub fn networks() -> Vec<Network> {
+    vec![
+        Network {
+            name: "testnet".to_owned(),
+            chain_id: 5611,
+            chain_name: "opBNB Testnet".to_owned(),
+            currency: "tBNB".to_owned(),
+            contract: "0xDbc0DdDd17C79dC029C4FD005F40eAC1DAB9E6Ba".to_owned(),
+            market: "https://aca.zypher.dev/rpc".to_owned(),
+            rpc: "https://opbnb-testnet-rpc.bnbchain.org".to_owned(),
+            leaderboard: String::new(),
+        },
+        Network {
+            name: "localhost".to_owned(),
+            chain_id: 31337,
+            chain_name: "Localhost".to_owned(),
+            currency: "ETH".to_owned(),
+            contract: "0xe7f1725E7734CE288F8367e1Bb143E90bb3F0512".to_owned(),
+            market: "http://127.0.0.1:8080".to_owned(),
+            rpc: "http://127.0.0.1:8545".to_owned(),
+            leaderboard: "http://127.0.0.1:8090".to_owned(),
+        },
+    ]
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct Config {
+    pub network: Network,
+    // all profiles, builtin and from the config file
+    pub networks: Vec<Network>,
+}
+
+/// the config file in native
+#[derive(Default, Deserialize)]
+struct ConfigFile {
+    network: Option<String>,
+    #[serde(default)]
+    networks: Vec<Network>,
+}
+
+impl Config {
+    /// select the profile by `network`, then override it by `chain_id`,
+    /// `contract`, `market`, `rpc` and `leaderboard`
+    pub fn load() -> Self {
+        let file = load_file();
+        let mut networks = networks();
+        for network in file.networks {
+            networks.retain(|n| n.name != network.name);
+            networks.push(network);
+        }
+
+        let name = param("network").or(file.network);
+        let mut network = name
+            .and_then(|name| networks.iter().find(|n| n.name == name).cloned())
+            .unwrap_or_else(|| networks[0].clone());
+        if let Some(chain_id) = param("chain_id").and_then(|v| v.parse().ok()) {
+            network.chain_id = chain_id;
+        }
+        if let Some(contract) = param("contract") {
+            network.contract = contract;
+        }
+        if let Some(market) = param("market") {
+            network.market = market;
+        }
+        if let Some(rpc) = param("rpc") {
+            network.rpc = rpc;
+        }
+        if let Some(leaderboard) = param("leaderboard") {
+            network.leaderboard = leaderboard;
+        }
+
+        info!("Network: {:?}", network);
+        Self { network, networks }
+    }
+
+    /// the profile of chain, the selected one first
+    pub fn by_chain(&self, chain_id: u64) -> Option<&Network> {
+        if self.network.chain_id == chain_id {
+            return Some(&self.network);
+        }
+        self.networks.iter().find(|n| n.chain_id == chain_id)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_file() -> ConfigFile {
+    ConfigFile::default()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn param(key: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(key)
+}
+
+/// `ACA_CONFIG` file, default `$XDG_CONFIG_HOME/alien-cake-addict/config.json`
+#[cfg(not(target_arch = "wasm32"))]
+fn load_file() -> ConfigFile {
+    let path = std::env::var("ACA_CONFIG")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|_| crate::storage::config_dir().join("config.json"));
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+            error!("Config {:?}: {}", path, err);
+            ConfigFile::default()
+        }),
+        Err(_) => ConfigFile::default(),
+    }
+}
+
+/// `ACA_NETWORK`, `ACA_CHAIN_ID`, `ACA_CONTRACT`, `ACA_MARKET`, `ACA_RPC` and `ACA_LEADERBOARD`
+#[cfg(not(target_arch = "wasm32"))]
+fn param(key: &str) -> Option<String> {
+    std::env::var(format!("ACA_{}", key.to_uppercase())).ok()
+}
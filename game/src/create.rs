@@ -0,0 +1,285 @@
+//! The room creation dialog: ticket with the ERC20 balance and approval,
+//! viewable and max players when the contract has `createRoomWithParams`.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_web3::H160;
+use ethers_core::{
+    abi::{parse_abi, Abi, Token},
+    types::{Address, U256},
+    utils::{format_units, parse_units},
+};
+use serde_json::json;
+
+use crate::{
+    rpc::{result_bytes, ChainRpc},
+    tx::{Action, Transactions},
+    wallet::Wallet,
+    Game,
+};
+
+const ABI: &[&str] = &[
+    "function token() view returns (address)",
+    "function decimals() view returns (uint8)",
+    "function playerLimit() view returns (uint256)",
+    "function createRoom(uint256 ticket, bool viewable, address peer, bytes32 pk, bytes32 salt) returns (uint256)",
+    "function createRoomWithParams(uint256 ticket, bool viewable, address peer, bytes32 pk, uint256 limit, bytes params) returns (uint256)",
+    "function balanceOf(address account) view returns (uint256)",
+    "function allowance(address owner, address spender) view returns (uint256)",
+    "function approve(address spender, uint256 amount) returns (bool)",
+];
+
+#[derive(Resource)]
+pub struct CreateRoom {
+    abi: Abi,
+    rpc: ChainRpc,
+    open: bool,
+    // inputs
+    ticket: String,
+    viewable: bool,
+    max_players: usize,
+    // from chain
+    token: Option<Address>,
+    decimals: Option<u32>,
+    // the contract has createRoomWithParams
+    with_params: Option<bool>,
+    player_limit: usize,
+    balance: Option<U256>,
+    allowance: Option<U256>,
+}
+
+impl Default for CreateRoom {
+    fn default() -> Self {
+        Self {
+            abi: parse_abi(ABI).expect("invalid create abi"),
+            rpc: ChainRpc::default(),
+            open: false,
+            ticket: "0".to_owned(),
+            viewable: false,
+            max_players: 0,
+            token: None,
+            decimals: None,
+            with_params: None,
+            player_limit: 0,
+            balance: None,
+            allowance: None,
+        }
+    }
+}
+
+impl CreateRoom {
+    pub fn open(&mut self, game: &Game) {
+        self.open = true;
+        self.refresh(game);
+    }
+
+    fn encode(&self, name: &str, params: &[Token]) -> Vec<u8> {
+        self.abi
+            .function(name)
+            .and_then(|f| f.encode_input(params))
+            .unwrap_or_default()
+    }
+
+    /// read the token, player limit, balance and allowance
+    fn refresh(&self, game: &Game) {
+        let (url, contract) = (&game.network.rpc, &game.network.contract);
+        if self.with_params.is_none() {
+            self.rpc
+                .request(url, "code", "eth_getCode", json!([contract, "latest"]));
+        }
+        self.rpc
+            .call(url, "token", contract, &self.encode("token", &[]));
+        self.rpc.call(
+            url,
+            "playerLimit",
+            contract,
+            &self.encode("playerLimit", &[]),
+        );
+
+        let (Some(token), Ok(account), Ok(spender)) = (
+            self.token,
+            game.account.parse::<Address>(),
+            contract.parse::<Address>(),
+        ) else {
+            return;
+        };
+        let token = format!("{:?}", token);
+        if self.decimals.is_none() {
+            self.rpc
+                .call(url, "decimals", &token, &self.encode("decimals", &[]));
+        }
+        let data = self.encode("balanceOf", &[Token::Address(account)]);
+        self.rpc.call(url, "balanceOf", &token, &data);
+        let data = self.encode(
+            "allowance",
+            &[Token::Address(account), Token::Address(spender)],
+        );
+        self.rpc.call(url, "allowance", &token, &data);
+    }
+
+    fn receive(&mut self, game: &Game) {
+        while let Some((id, result)) = self.rpc.recv() {
+            let output = match result {
+                Ok(output) => result_bytes(&output),
+                Err(err) => {
+                    warn!("Create room {}: {}", id, err);
+                    continue;
+                }
+            };
+            // the selector is in the dispatcher of the contract code
+            if id == "code" {
+                let selector = self
+                    .abi
+                    .function("createRoomWithParams")
+                    .map(|f| f.short_signature())
+                    .unwrap_or_default();
+                self.with_params = Some(output.windows(4).any(|w| w == selector));
+                continue;
+            }
+            let Some(value) = self
+                .abi
+                .function(&id)
+                .ok()
+                .and_then(|f| f.decode_output(&output).ok())
+                .and_then(|tokens| tokens.into_iter().next())
+            else {
+                continue;
+            };
+
+            match id.as_str() {
+                "token" => {
+                    let first = self.token.is_none();
+                    self.token = value.into_address();
+                    if first {
+                        self.refresh(game);
+                    }
+                }
+                "playerLimit" => {
+                    self.player_limit = value.into_uint().unwrap_or_default().as_usize();
+                    if self.max_players == 0 || self.max_players > self.player_limit {
+                        self.max_players = self.player_limit;
+                    }
+                }
+                "decimals" => self.decimals = value.into_uint().map(|v| v.as_u32()),
+                "balanceOf" => self.balance = value.into_uint(),
+                "allowance" => self.allowance = value.into_uint(),
+                _ => {}
+            }
+        }
+    }
+
+    /// the createRoom transaction, createRoomWithParams for less players
+    fn create_data(&self, game: &Game, ticket: U256) -> Vec<u8> {
+        let peer = Address::from(game.peer.peer_id().0);
+        let mut tokens = vec![
+            Token::Uint(ticket),
+            Token::Bool(self.viewable),
+            Token::Address(peer),
+            Token::FixedBytes(vec![0u8; 32]),
+        ];
+        if self.with_params != Some(true) || self.max_players == self.player_limit {
+            tokens.push(Token::FixedBytes(vec![0u8; 32]));
+            self.encode("createRoom", &tokens)
+        } else {
+            tokens.push(Token::Uint(self.max_players.into()));
+            tokens.push(Token::Bytes(vec![]));
+            self.encode("createRoomWithParams", &tokens)
+        }
+    }
+}
+
+pub fn refresh(create: Res<CreateRoom>, game: Res<Game>) {
+    if create.open {
+        create.refresh(&game);
+    }
+}
+
+pub fn show(
+    mut contexts: EguiContexts,
+    mut create: ResMut<CreateRoom>,
+    game: Res<Game>,
+    wallet: Wallet,
+    mut transactions: ResMut<Transactions>,
+) {
+    create.receive(&game);
+    if !create.open {
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Create Room")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            let create = &mut *create;
+            ui.horizontal(|ui| {
+                ui.label("Ticket:");
+                ui.text_edit_singleline(&mut create.ticket);
+            });
+            let Some(decimals) = create.decimals else {
+                ui.label("Loading token...");
+                return;
+            };
+            let units = |v: Option<U256>| {
+                v.and_then(|v| format_units(v, decimals).ok())
+                    .unwrap_or("...".to_owned())
+            };
+            ui.label(format!(
+                "Balance: {}, allowance: {}",
+                units(create.balance),
+                units(create.allowance)
+            ));
+            ui.checkbox(&mut create.viewable, "Viewable");
+            if create.with_params == Some(true) && create.player_limit > 0 {
+                ui.add(
+                    egui::Slider::new(&mut create.max_players, 1..=create.player_limit)
+                        .text("Max players"),
+                );
+            }
+
+            let Ok(ticket) = parse_units(create.ticket.trim(), decimals).map(U256::from) else {
+                ui.label("Invalid ticket");
+                return;
+            };
+
+            if !ticket.is_zero() {
+                let (Some(token), Some(balance), Some(allowance)) =
+                    (create.token, create.balance, create.allowance)
+                else {
+                    ui.label("Loading token...");
+                    return;
+                };
+                if balance < ticket {
+                    ui.label("Insufficient balance");
+                    return;
+                }
+                if allowance < ticket {
+                    let enabled = !transactions.is_pending(&Action::Approve);
+                    if ui
+                        .add_enabled(enabled, egui::Button::new("Approve"))
+                        .clicked()
+                    {
+                        let spender = game.network.contract.parse().unwrap_or_default();
+                        let data = create
+                            .encode("approve", &[Token::Address(spender), Token::Uint(ticket)]);
+                        let to = H160(token.0);
+                        transactions.send(&wallet, &game.account, Action::Approve, to, data);
+                    }
+                    return;
+                }
+            }
+
+            let enabled = !transactions.is_pending(&Action::CreateRoom);
+            if ui
+                .add_enabled(enabled, egui::Button::new("Create"))
+                .clicked()
+            {
+                let data = create.create_data(&game, ticket);
+                let to = game.contract.address;
+                transactions.send(&wallet, &game.account, Action::CreateRoom, to, data);
+                create.open = false;
+            }
+        });
+    if !open {
+        create.open = false;
+    }
+}
@@ -1,8 +1,9 @@
 use bevy::prelude::*;
-use bevy_web3::{EthWallet, RecvError};
+use bevy_web3::RecvError;
 
 use crate::{
     style::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
+    wallet::Wallet,
     Game, GameState,
 };
 
@@ -62,7 +63,7 @@ pub fn connect_wallet(mut commands: Commands) {
 }
 
 pub fn connect_button(
-    wallet: Res<EthWallet>,
+    wallet: Wallet,
     mut interaction_query: Query<
         (
             &Interaction,
@@ -100,7 +101,7 @@ pub fn connect_button(
 pub fn wallet_account(
     mut next_state: ResMut<NextState<GameState>>,
     mut game: ResMut<Game>,
-    mut channel: ResMut<EthWallet>,
+    mut channel: Wallet,
 ) {
     match channel.recv_account() {
         Ok((account, network)) => {
@@ -108,6 +109,7 @@ pub fn wallet_account(
             // TODO
             game.account = account;
             game.chain = network;
+            game.practice = false;
             next_state.set(GameState::Listing);
         }
         Err(RecvError::Empty) => {}
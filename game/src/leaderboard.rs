@@ -0,0 +1,175 @@
+//! The global leaderboard from the leaderboard API of the network, the top
+//! players and the rank of the account.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use ethers_core::{types::U256, utils::format_units};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+
+use crate::Game;
+
+/// players of the top list
+const TOP_LIMIT: usize = 20;
+
+#[derive(Clone, Debug, Deserialize)]
+struct Row {
+    rank: usize,
+    player: String,
+    win: String,
+    reward: String,
+    games: u64,
+}
+
+type Response = (&'static str, Result<Value, String>);
+
+#[derive(Resource)]
+pub struct Leaderboard {
+    sender: Sender<Response>,
+    receiver: Mutex<Receiver<Response>>,
+    block: u64,
+    total: usize,
+    top: Vec<Row>,
+    // the rank of account, none when not ranked
+    mine: Option<Row>,
+    error: Option<String>,
+}
+
+impl Default for Leaderboard {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+            block: 0,
+            total: 0,
+            top: vec![],
+            mine: None,
+            error: None,
+        }
+    }
+}
+
+impl Leaderboard {
+    fn get(&self, id: &'static str, url: String) {
+        let sender = self.sender.clone();
+        ehttp::fetch(ehttp::Request::get(url), move |response| {
+            let result = response.and_then(|r| {
+                if r.ok {
+                    r.json::<Value>().map_err(|e| e.to_string())
+                } else {
+                    Err(format!("{} {}", r.status, r.status_text))
+                }
+            });
+            let _ = sender.send((id, result));
+        });
+    }
+
+    fn fetch(&self, game: &Game) {
+        let api = game.network.leaderboard.trim_end_matches('/');
+        self.get("top", format!("{}/leaderboard?limit={}", api, TOP_LIMIT));
+        if !game.account.is_empty() {
+            self.get("mine", format!("{}/players/{}?limit=0", api, game.account));
+        }
+    }
+
+    fn receive(&mut self) {
+        let Ok(receiver) = self.receiver.lock() else {
+            return;
+        };
+        let responses: Vec<Response> = receiver.try_iter().collect();
+        drop(receiver);
+
+        for (id, result) in responses {
+            match (id, result) {
+                ("top", Ok(value)) => {
+                    self.block = value["block"].as_u64().unwrap_or(0);
+                    self.total = value["total"].as_u64().unwrap_or(0) as usize;
+                    self.top = serde_json::from_value(value["players"].clone()).unwrap_or_default();
+                    self.error = None;
+                }
+                ("top", Err(err)) => self.error = Some(err),
+                ("mine", Ok(value)) => self.mine = serde_json::from_value(value).ok(),
+                // not ranked
+                ("mine", Err(_)) => self.mine = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// the reward in token units
+fn units(amount: &str) -> String {
+    U256::from_dec_str(amount)
+        .ok()
+        .and_then(|a| format_units(a, 18).ok())
+        .unwrap_or_else(|| amount.to_owned())
+}
+
+/// fetch the leaderboard when the network has the API
+pub fn refresh(game: Res<Game>, leaderboard: Res<Leaderboard>) {
+    if !game.network.leaderboard.is_empty() {
+        leaderboard.fetch(&game);
+    }
+}
+
+pub fn show(mut contexts: EguiContexts, game: Res<Game>, mut leaderboard: ResMut<Leaderboard>) {
+    if game.network.leaderboard.is_empty() {
+        return;
+    }
+    leaderboard.receive();
+
+    egui::Window::new("Leaderboard")
+        .default_open(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-4.0, -4.0))
+        .show(contexts.ctx_mut(), |ui| {
+            if let Some(err) = &leaderboard.error {
+                ui.label(format!("Leaderboard unavailable: {}", err));
+                return;
+            }
+            if leaderboard.top.is_empty() {
+                ui.label("No ranked players");
+            }
+
+            egui::Grid::new("leaderboard").striped(true).show(ui, |ui| {
+                ui.label("Rank");
+                ui.label("Player");
+                ui.label("Win");
+                ui.label("Reward");
+                ui.label("Games");
+                ui.end_row();
+                for row in &leaderboard.top {
+                    let you = row.player.eq_ignore_ascii_case(&game.account);
+                    let player = if you {
+                        egui::RichText::new(format!("{} (you)", row.player)).strong()
+                    } else {
+                        egui::RichText::new(&row.player)
+                    };
+                    ui.label(format!("#{}", row.rank));
+                    ui.label(player);
+                    ui.label(&row.win);
+                    ui.label(units(&row.reward));
+                    ui.label(row.games.to_string());
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+            match &leaderboard.mine {
+                Some(row) => ui.label(format!(
+                    "You: #{} of {}, win {}, reward {} in {} games",
+                    row.rank,
+                    leaderboard.total,
+                    row.win,
+                    units(&row.reward),
+                    row.games
+                )),
+                None => ui.label(format!("You are not ranked, {} players", leaderboard.total)),
+            };
+            ui.small(format!("Synced to block {}", leaderboard.block));
+        });
+}
@@ -1,24 +1,37 @@
 //! Eat the cakes. Eat them all. An example 3D game.
 
+mod config;
+mod create;
 mod init;
+mod leaderboard;
 mod list;
+mod network;
 mod over;
 mod play;
+mod practice;
+mod rpc;
+mod storage;
 mod style;
+mod tween;
+mod tx;
 mod wait;
+mod wallet;
 
 use bevy::{prelude::*, time::common_conditions::on_timer};
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
-use bevy_web3::{Contract, EthWallet, WalletPlugin};
-use std::collections::{BTreeMap, HashMap};
+use bevy_web3::{Contract, WalletPlugin};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use z4_bevy::{
     fetch_room_market, fetch_room_status, handle_room_market, handle_room_status, PeerKey, RoomId,
     RoomMarket, Z4ClientPlugin,
 };
 use z4_types::contracts::SIMPLE_GAME_ABI;
 
+use config::{Config, Network};
 use play::{Cake, Cell, Player};
+use storage::{Storage, LAST_SERVER, PEER_KEY};
 use style::{BOARD_SIZE_I, BOARD_SIZE_J};
+use wallet::{LocalWallet, Wallet};
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 enum GameState {
@@ -31,43 +44,88 @@ enum GameState {
 }
 
 pub fn start() {
-    App::new()
-        .add_plugins(DefaultPlugins)
+    let config = Config::load();
+    let mut app = App::new();
+    // the local key wallet, or the browser wallet
+    if let Some(wallet) = LocalWallet::from_env(&config.network.rpc) {
+        app.insert_resource(wallet);
+    } else {
+        app.add_plugins(WalletPlugin);
+    }
+
+    app.add_plugins(DefaultPlugins)
         .add_plugins(EguiPlugin)
         .add_plugins(Z4ClientPlugin)
-        .add_plugins(WalletPlugin)
         .init_state::<GameState>()
-        .insert_resource(Game::init())
-        .add_systems(Startup, (setup_2d_cameras, init))
+        .insert_resource(Game::init(&config.network))
+        .insert_resource(config)
+        .init_resource::<create::CreateRoom>()
+        .init_resource::<tx::Transactions>()
+        .init_resource::<list::Viewables>()
+        .init_resource::<list::Lobby>()
+        .init_resource::<play::QueueStats>()
+        .init_resource::<play::MessageQueue>()
+        .init_resource::<tween::AlienModel>()
+        .init_resource::<leaderboard::Leaderboard>()
+        .add_systems(Startup, init)
+        .add_systems(OnEnter(GameState::Initing), setup_2d_cameras)
         .add_systems(
             Update,
             (
                 init::connect_wallet,
                 init::connect_button,
                 init::wallet_account,
+                practice::practice_button,
             )
                 .run_if(in_state(GameState::Initing)),
         )
         .add_systems(OnExit(GameState::Initing), teardown)
-        .add_systems(OnEnter(GameState::Listing), setup_2d_cameras)
+        .add_systems(OnEnter(GameState::Listing), (setup_2d_cameras, list::setup))
         .add_systems(
             Update,
-            (list::show, list::join, list::create, handle_room_market)
+            (
+                list::update_texts.run_if(resource_changed::<Game>),
+                list::rebuild
+                    .run_if(resource_changed::<RoomMarket>.or_else(resource_changed::<Game>)),
+                list::show.after(list::rebuild),
+                list::create,
+                create::show,
+                leaderboard::show,
+                handle_room_market,
+                network::show,
+                network::sync.after(tracing_account),
+            )
                 .run_if(in_state(GameState::Listing)),
         )
         .add_systems(
             Update,
-            (fetch_room_market, tracing_account)
+            (
+                fetch_room_market,
+                tracing_account,
+                create::refresh,
+                list::fetch_viewables,
+            )
                 .run_if(in_state(GameState::Listing))
                 .run_if(on_timer(std::time::Duration::from_secs(2))),
         )
+        .add_systems(OnEnter(GameState::Listing), leaderboard::refresh)
+        .add_systems(
+            Update,
+            leaderboard::refresh
+                .run_if(in_state(GameState::Listing))
+                .run_if(on_timer(std::time::Duration::from_secs(10))),
+        )
         .add_systems(OnExit(GameState::Listing), (teardown, list::cleanup))
         .add_systems(OnEnter(GameState::Waiting), wait::setup)
         .add_systems(
             Update,
             (
                 back_button,
-                wait::show,
+                wait::ready.run_if(resource_changed::<RoomMarket>),
+                wait::show
+                    .after(wait::ready)
+                    .run_if(resource_changed::<RoomMarket>.or_else(resource_changed::<Game>)),
+                wait::start_label,
                 wait::start_button,
                 wait::countdown,
                 fetch_room_status,
@@ -85,14 +143,51 @@ pub fn start() {
                 play::focus_camera,
                 play::rotate_cake,
                 play::scoreboard_system,
+                play::spectator,
                 play::ws_message,
+                play::debug_overlay,
             )
                 .run_if(in_state(GameState::Playing)),
         )
-        .add_systems(OnExit(GameState::Playing), (teardown, play::cleanup))
+        .add_systems(
+            Update,
+            (
+                tween::animate.after(play::ws_message),
+                tween::attach_animators,
+                tween::play_clips.after(tween::attach_animators),
+            )
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (practice::tick, practice::practice_message)
+                .run_if(in_state(GameState::Playing))
+                .run_if(resource_exists::<practice::Practice>),
+        )
+        .add_systems(
+            OnExit(GameState::Playing),
+            (teardown, play::cleanup, practice::cleanup),
+        )
         .add_systems(
             OnEnter(GameState::GameOver),
-            (setup_2d_cameras, over::display_score),
+            (
+                setup_2d_cameras,
+                over::display_score,
+                over::setup_settlement,
+            ),
+        )
+        .add_systems(
+            Update,
+            over::show_settlement
+                .run_if(in_state(GameState::GameOver))
+                .run_if(resource_exists::<over::Settlement>),
+        )
+        .add_systems(
+            Update,
+            over::poll_settlement
+                .run_if(in_state(GameState::GameOver))
+                .run_if(resource_exists::<over::Settlement>)
+                .run_if(on_timer(std::time::Duration::from_secs(2))),
         )
         .add_systems(
             Update,
@@ -102,13 +197,20 @@ pub fn start() {
                 bevy::window::close_on_esc,
             ),
         )
-        .add_systems(OnExit(GameState::GameOver), teardown)
+        .add_systems(OnExit(GameState::GameOver), (teardown, over::cleanup))
+        .add_systems(Update, (tx::receive, tx::toasts).chain())
+        .add_systems(
+            Update,
+            tx::poll.run_if(on_timer(std::time::Duration::from_secs(2))),
+        )
         .run();
 }
 
 #[derive(Resource)]
 struct Game {
     chain: u64,
+    /// the network profile of config
+    network: Network,
     account: String,
     contract: Contract,
     room: RoomId,
@@ -119,70 +221,102 @@ struct Game {
     board: Vec<Vec<Cell>>,
     peer: PeerKey,
     player: Player,
+    /// sequence of the last signed move request
+    move_seq: u64,
+    /// own moves not confirmed by the sequencer
+    prediction: play::Prediction,
     opponents: HashMap<String, Player>,
     cakes: HashMap<u32, Cake>,
     cake_last: Option<Entity>,
     cake_handle: Handle<Scene>,
     scores: BTreeMap<String, u32>,
+    /// the final standings when over
+    standings: Option<play::Standings>,
+    /// server bot players in the room
+    bots: HashSet<String>,
+    /// operations hash chain heads from sequencer, checked with the result
+    heads: play::Heads,
     camera_should_focus: Vec3,
     camera_is_focus: Vec3,
+    storage: Storage,
+    /// playing the local practice, no wallet and sequencer
+    practice: bool,
+    /// watching the viewable room read-only, all players are opponents
+    spectating: bool,
+    /// the player followed by the camera when spectating
+    follow: Option<String>,
 }
 
 impl Game {
-    fn init() -> Game {
-        let window = web_sys::window().unwrap();
-        let peer = if let Ok(Some(ss)) = window.session_storage() {
-            if let Ok(Some(s)) = ss.get("peer-key") {
-                let bytes = hex::decode(&s).unwrap();
-                PeerKey::from_db_bytes(&bytes).unwrap()
-            } else {
-                // generate key
-                let peer = PeerKey::generate(&mut rand::thread_rng());
-                let bytes = peer.to_db_bytes();
-                let s = hex::encode(&bytes);
-                let _ = ss.set("peer-key", &s);
-                peer
-            }
+    fn init(network: &Network) -> Game {
+        let mut storage = Storage::load();
+        let stored = storage
+            .get(PEER_KEY)
+            .and_then(|s| hex::decode(s).ok())
+            .and_then(|bytes| PeerKey::from_db_bytes(&bytes).ok());
+        let peer = if let Some(peer) = stored {
+            peer
         } else {
-            PeerKey::generate(&mut rand::thread_rng())
+            // generate key
+            let peer = PeerKey::generate(&mut rand::thread_rng());
+            storage.set(PEER_KEY, &hex::encode(peer.to_db_bytes()));
+            peer
         };
-
-        let address = "0xDbc0DdDd17C79dC029C4FD005F40eAC1DAB9E6Ba";
-        // let address = "0xe7f1725E7734CE288F8367e1Bb143E90bb3F0512"; // localhost
+        let server = storage.get(LAST_SERVER).unwrap_or_default();
 
         Game {
             peer,
-            contract: Contract::load(address, SIMPLE_GAME_ABI.as_bytes()),
+            contract: Contract::load(&network.contract, SIMPLE_GAME_ABI.as_bytes()),
             chain: 0,
+            network: network.clone(),
             account: Default::default(),
             room: 0,
             countdown: 0,
             listing_entity: None,
             waiting_entity: None,
-            server: "".to_owned(),
+            server,
             board: vec![vec![Cell { height: 0.0 }; BOARD_SIZE_J]; BOARD_SIZE_I],
             player: Player::default(),
+            move_seq: 0,
+            prediction: Default::default(),
             opponents: HashMap::default(),
             cakes: HashMap::default(),
             cake_last: None,
             cake_handle: Default::default(),
             scores: BTreeMap::default(),
+            standings: None,
+            bots: HashSet::default(),
+            heads: Default::default(),
             camera_should_focus: Vec3::default(),
             camera_is_focus: Vec3::default(),
+            practice: false,
+            spectating: false,
+            follow: None,
+            storage,
+        }
+    }
+
+    /// practice has no room listing, back to the start
+    fn back_state(&self) -> GameState {
+        if self.practice {
+            GameState::Initing
+        } else {
+            GameState::Listing
         }
     }
 
     pub fn is_chain(&self) -> bool {
-        self.chain == 5611 // opBNB Testnet
+        self.chain == self.network.chain_id
     }
 }
 
 fn init(mut room_market: ResMut<RoomMarket>, game: Res<Game>) {
-    // TODO game init from chain
-    room_market.url = "https://aca.zypher.dev/rpc".to_owned(); // testnet
-    //room_market.url = "http://127.0.0.1:8080".to_owned(); // localhost
+    setup_market(&mut room_market, &game);
+}
 
-    // setup room_market
+/// setup room_market of the game network
+fn setup_market(room_market: &mut RoomMarket, game: &Game) {
+    room_market.url = game.network.market.clone();
     room_market.game = game.contract.address();
     room_market.contract = Contract::load(&room_market.game, SIMPLE_GAME_ABI.as_bytes());
 }
@@ -198,17 +332,21 @@ fn teardown(mut commands: Commands, entities: Query<Entity, Without<Window>>) {
     }
 }
 
-fn back_button(mut contexts: EguiContexts, mut next_state: ResMut<NextState<GameState>>) {
+fn back_button(
+    mut contexts: EguiContexts,
+    game: Res<Game>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
     egui::Area::new("BACK")
         .fixed_pos(egui::pos2(4.0, 4.0))
         .show(contexts.ctx_mut(), |ui| {
             if ui.button("BACK").clicked() {
-                next_state.set(GameState::Listing);
+                next_state.set(game.back_state());
             }
         });
 }
 
-fn tracing_account(mut game: ResMut<Game>, mut channel: ResMut<EthWallet>) {
+fn tracing_account(mut game: ResMut<Game>, mut channel: Wallet) {
     channel.connect();
     match channel.recv_account() {
         Ok((account, network)) => {
@@ -1,33 +1,121 @@
 use bevy::prelude::*;
 
 use bevy_egui::{egui, EguiContexts};
-use bevy_web3::{EthWallet, RecvError, Token, H160, U256};
-use z4_bevy::RoomMarket;
+use bevy_web3::{Token, H160};
+use ethers_core::abi::{parse_abi, Abi};
+use std::collections::HashMap;
+use z4_bevy::{RoomId, RoomMarket};
 
 use crate::{
-    style::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
+    create::CreateRoom,
+    rpc::{result_bytes, ChainRpc},
+    style::{set_text, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
+    tx::{Action, Transactions},
+    wallet::Wallet,
     Game, GameState,
 };
 
-pub fn cleanup(mut game: ResMut<Game>) {
-    game.listing_entity = None;
+/// the public getter of room, the first is the viewable flag
+const ROOMS_ABI: &[&str] = &[
+    "function rooms(uint256 roomId) view returns (bool, uint256, uint256, address, uint256, uint256, bytes, uint8)",
+];
+
+/// The viewable flags of the playing rooms, read from the contract once.
+#[derive(Resource)]
+pub struct Viewables {
+    abi: Abi,
+    rpc: ChainRpc,
+    // none when requested and not received
+    rooms: HashMap<RoomId, Option<bool>>,
 }
 
-pub fn show(
-    mut commands: Commands,
-    mut contexts: EguiContexts,
-    mut market: ResMut<RoomMarket>,
-    wallet: Res<EthWallet>,
-    mut game: ResMut<Game>,
-    mut next_state: ResMut<NextState<GameState>>,
-) {
-    if let Some(entity) = game.listing_entity {
-        if commands.get_entity(entity).is_some() {
-            commands.entity(entity).despawn_recursive();
+impl Default for Viewables {
+    fn default() -> Self {
+        Self {
+            abi: parse_abi(ROOMS_ABI).expect("invalid rooms abi"),
+            rpc: ChainRpc::default(),
+            rooms: HashMap::new(),
+        }
+    }
+}
+
+impl Viewables {
+    fn viewable(&self, room: RoomId) -> bool {
+        self.rooms.get(&room).copied().flatten().unwrap_or(false)
+    }
+
+    fn receive(&mut self) {
+        while let Some((id, result)) = self.rpc.recv() {
+            let Ok(room) = id.parse::<RoomId>() else {
+                continue;
+            };
+            let viewable = result
+                .ok()
+                .and_then(|r| {
+                    self.abi
+                        .function("rooms")
+                        .ok()?
+                        .decode_output(&result_bytes(&r))
+                        .ok()
+                })
+                .and_then(|tokens| tokens.first()?.clone().into_bool());
+            match viewable {
+                Some(viewable) => {
+                    self.rooms.insert(room, Some(viewable));
+                }
+                // request it again
+                None => {
+                    self.rooms.remove(&room);
+                }
+            }
         }
     }
+}
+
+pub fn cleanup(mut game: ResMut<Game>) {
+    game.listing_entity = None;
+}
+
+/// the lobby texts, updated in place
+#[derive(Component)]
+pub enum LobbyText {
+    Chain,
+    Account,
+    Peer,
+}
+
+/// a room of the market in the lobby windows
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoomSummary {
+    room: RoomId,
+    label: String,
+    joined: bool,
+    websocket: Option<String>,
+}
+
+/// The market rooms by status, rebuilt only when the market or game changes.
+#[derive(Resource, Default, PartialEq, Eq)]
+pub struct Lobby {
+    pendings: Vec<RoomSummary>,
+    waitings: Vec<RoomSummary>,
+    playings: Vec<RoomSummary>,
+}
+
+pub fn setup(mut commands: Commands, mut game: ResMut<Game>) {
+    let text = |text: LobbyText| {
+        (
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 30.,
+                    ..default()
+                },
+            ),
+            text,
+        )
+    };
 
-    game.waiting_entity = Some(
+    game.listing_entity = Some(
         commands
             .spawn(NodeBundle {
                 style: Style {
@@ -41,37 +129,9 @@ pub fn show(
                 ..default()
             })
             .with_children(|parent| {
-                parent.spawn(TextBundle::from_section(
-                    format!(
-                        "Network Chain id: {} - {}",
-                        game.chain,
-                        if game.is_chain() {
-                            "opBNB Testnet"
-                        } else {
-                            "NOT Support !!!"
-                        }
-                    ),
-                    TextStyle {
-                        font_size: 30.,
-                        ..default()
-                    },
-                ));
-
-                parent.spawn(TextBundle::from_section(
-                    format!("Account: {}", game.account),
-                    TextStyle {
-                        font_size: 30.,
-                        ..default()
-                    },
-                ));
-
-                parent.spawn(TextBundle::from_section(
-                    format!("Local peer: {}", game.peer.peer_id().to_hex()),
-                    TextStyle {
-                        font_size: 30.,
-                        ..default()
-                    },
-                ));
+                parent.spawn(text(LobbyText::Chain));
+                parent.spawn(text(LobbyText::Account));
+                parent.spawn(text(LobbyText::Peer));
 
                 parent
                     .spawn(ButtonBundle {
@@ -103,115 +163,170 @@ pub fn show(
             })
             .id(),
     );
+}
 
-    if game.is_chain() {
-        let rooms = market.rooms.clone(); // TODO only clone waiting rooms
-        let mut pendings = vec![];
-        let mut waitings = vec![];
-        let mut playings = vec![];
-
-        for room in rooms {
-            if room.websocket.is_some() {
-                playings.push(room);
-                continue;
-            }
+/// update the lobby texts when the game changes
+pub fn update_texts(game: Res<Game>, mut texts: Query<(&LobbyText, Mut<Text>)>) {
+    for (kind, text) in &mut texts {
+        let value = match kind {
+            LobbyText::Chain => format!(
+                "Network Chain id: {} - {}",
+                game.chain,
+                if game.is_chain() {
+                    game.network.chain_name.as_str()
+                } else {
+                    "NOT Support !!!"
+                }
+            ),
+            LobbyText::Account => format!("Account: {}", game.account),
+            LobbyText::Peer => format!("Local peer: {}", game.peer.peer_id().to_hex()),
+        };
+        set_text(text, &value);
+    }
+}
 
-            if room.players.contains(&game.account) {
-                waitings.push(room);
+/// split the market rooms by status when the market or game changes
+pub fn rebuild(market: Res<RoomMarket>, game: Res<Game>, mut lobby: ResMut<Lobby>) {
+    let mut rooms = Lobby::default();
+    if game.is_chain() {
+        for room in &market.rooms {
+            let summary = RoomSummary {
+                room: room.room,
+                label: format!("Room: {}, players: {}", room.room, room.players.len()),
+                joined: room.players.contains(&game.account),
+                websocket: room.websocket.clone(),
+            };
+            if summary.websocket.is_some() {
+                rooms.playings.push(summary);
+            } else if summary.joined {
+                rooms.waitings.push(summary);
             } else {
-                pendings.push(room)
+                rooms.pendings.push(summary);
             }
         }
+    }
+    lobby.set_if_neq(rooms);
+}
 
-        egui::Window::new("Pending Rooms").show(contexts.ctx_mut(), |ui| {
-            if pendings.is_empty() {
-                ui.label("No rooms, waiting...");
-            }
+/// read the viewable flags of the new playing rooms of others
+pub fn fetch_viewables(game: Res<Game>, lobby: Res<Lobby>, mut viewables: ResMut<Viewables>) {
+    let Ok(function) = viewables.abi.function("rooms").cloned() else {
+        return;
+    };
+    for room in &lobby.playings {
+        if room.joined || viewables.rooms.contains_key(&room.room) {
+            continue;
+        }
+        let data = function
+            .encode_input(&[Token::Uint(room.room.into())])
+            .unwrap_or_default();
+        viewables.rpc.call(
+            &game.network.rpc,
+            &room.room.to_string(),
+            &game.network.contract,
+            &data,
+        );
+        viewables.rooms.insert(room.room, None);
+    }
+}
 
-            for room in pendings {
-                ui.horizontal(|ui| {
-                    ui.label(format!(
-                        "Room: {}, players: {}",
-                        room.room,
-                        room.players.len()
-                    ));
-
-                    if ui.button("Join").clicked() {
-                        // join room
-                        let pid_bytes = game.peer.peer_id().0;
-                        let data = game.contract.encode(
-                            "joinRoom",
-                            &[
-                                Token::Uint(room.room.into()),
-                                Token::Address(H160(pid_bytes)),
-                                Token::FixedBytes(vec![0u8;32]),
-                            ],
-                        );
-                        wallet.send(&game.account, game.contract.address, data);
-                    }
-                });
-            }
-        });
+pub fn show(
+    mut contexts: EguiContexts,
+    mut market: ResMut<RoomMarket>,
+    lobby: Res<Lobby>,
+    wallet: Wallet,
+    mut transactions: ResMut<Transactions>,
+    mut viewables: ResMut<Viewables>,
+    mut game: ResMut<Game>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    viewables.receive();
 
-        egui::Window::new("Waiting Rooms").show(contexts.ctx_mut(), |ui| {
-            if waitings.is_empty() {
-                ui.label("No rooms, waiting...");
-            }
+    if !game.is_chain() {
+        return;
+    }
+
+    egui::Window::new("Pending Rooms").show(contexts.ctx_mut(), |ui| {
+        if lobby.pendings.is_empty() {
+            ui.label("No rooms, waiting...");
+        }
 
-            for room in waitings {
-                ui.horizontal(|ui| {
-                    ui.label(format!(
-                        "Room: {}, players: {}",
-                        room.room,
-                        room.players.len()
-                    ));
+        for room in &lobby.pendings {
+            ui.horizontal(|ui| {
+                ui.label(&room.label);
 
-                    if ui.button("Waiting").clicked() {
-                        game.room = room.room;
-                        market.waiting = Some(room.clone());
-                        next_state.set(GameState::Waiting);
-                    }
-                });
-            }
-        });
+                let action = Action::JoinRoom(room.room);
+                let enabled = !transactions.is_pending(&action);
+                if ui.add_enabled(enabled, egui::Button::new("Join")).clicked() {
+                    // join room
+                    let pid_bytes = game.peer.peer_id().0;
+                    let data = game.contract.encode(
+                        "joinRoom",
+                        &[
+                            Token::Uint(room.room.into()),
+                            Token::Address(H160(pid_bytes)),
+                            Token::FixedBytes(vec![0u8; 32]),
+                        ],
+                    );
+                    let to = game.contract.address;
+                    transactions.send(&wallet, &game.account, action, to, data);
+                }
+            });
+        }
+    });
 
-        egui::Window::new("Playing Rooms").show(contexts.ctx_mut(), |ui| {
-            if playings.is_empty() {
-                ui.label("No rooms, waiting...");
-            }
+    egui::Window::new("Waiting Rooms").show(contexts.ctx_mut(), |ui| {
+        if lobby.waitings.is_empty() {
+            ui.label("No rooms, waiting...");
+        }
+
+        for room in &lobby.waitings {
+            ui.horizontal(|ui| {
+                ui.label(&room.label);
+
+                if ui.button("Waiting").clicked() {
+                    game.room = room.room;
+                    game.spectating = false;
+                    market.waiting = market.rooms.iter().find(|r| r.room == room.room).cloned();
+                    next_state.set(GameState::Waiting);
+                }
+            });
+        }
+    });
 
-            for room in playings {
-                ui.horizontal(|ui| {
-                    ui.label(format!(
-                        "Room: {}, players: {}",
-                        room.room,
-                        room.players.len()
-                    ));
+    egui::Window::new("Playing Rooms").show(contexts.ctx_mut(), |ui| {
+        if lobby.playings.is_empty() {
+            ui.label("No rooms, waiting...");
+        }
+
+        for room in &lobby.playings {
+            ui.horizontal(|ui| {
+                ui.label(&room.label);
 
+                let server = room.websocket.as_ref();
+                if room.joined {
                     if ui.button("Play").clicked() {
                         game.room = room.room;
-                        game.server = room.websocket.clone().unwrap();
+                        game.server = server.cloned().unwrap_or_default();
+                        game.spectating = false;
                         next_state.set(GameState::Playing);
                     }
-                });
-            }
-        });
-    }
-}
-
-pub fn join(wallet: Res<EthWallet>) {
-    match wallet.recv_transaction() {
-        Ok(tx) => {
-            info!("tx: {:?}", tx);
-            //
+                } else if viewables.viewable(room.room) {
+                    // connect read-only and watch the players
+                    if ui.button("Spectate").clicked() {
+                        game.room = room.room;
+                        game.server = server.cloned().unwrap_or_default();
+                        game.spectating = true;
+                        next_state.set(GameState::Playing);
+                    }
+                }
+            });
         }
-        Err(RecvError::Empty) => {}
-        Err(RecvError::Closed) => {}
-    }
+    });
 }
 
 pub fn create(
-    wallet: Res<EthWallet>,
+    mut create: ResMut<CreateRoom>,
     game: Res<Game>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, &mut BorderColor),
@@ -224,18 +339,7 @@ pub fn create(
                 *color = PRESSED_BUTTON.into();
                 border_color.0 = Color::RED;
 
-                // create room
-                let pid_bytes = game.peer.peer_id().0;
-                let data = game
-                    .contract
-                    .encode("createRoom", &[
-                        Token::Uint(U256::zero()),
-                        Token::Bool(false),
-                        Token::Address(H160(pid_bytes)),
-                        Token::FixedBytes(vec![0u8;32]),
-                        Token::FixedBytes(vec![0u8;32]),
-                    ]);
-                wallet.send(&game.account, game.contract.address, data);
+                create.open(&game);
             }
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON.into();
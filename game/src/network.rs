@@ -0,0 +1,46 @@
+//! Follow the wallet chain: select its network in the registry, and refresh
+//! the room market of the network.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_web3::Contract;
+use z4_bevy::RoomMarket;
+use z4_types::contracts::SIMPLE_GAME_ABI;
+
+use crate::{config::Config, setup_market, wallet::Wallet, Game};
+
+/// switch to the network of the wallet chain
+pub fn sync(config: Res<Config>, mut game: ResMut<Game>, mut market: ResMut<RoomMarket>) {
+    if game.chain == 0 || game.is_chain() {
+        return;
+    }
+
+    if let Some(network) = config.by_chain(game.chain).cloned() {
+        info!("Switch network: {} ({})", network.name, network.chain_id);
+        game.contract = Contract::load(&network.contract, SIMPLE_GAME_ABI.as_bytes());
+        game.network = network;
+
+        // rooms of the last network are gone
+        market.rooms.clear();
+        market.waiting = None;
+        setup_market(&mut market, &game);
+    }
+}
+
+/// the networks in registry, ask wallet to switch
+pub fn show(mut contexts: EguiContexts, config: Res<Config>, game: Res<Game>, wallet: Wallet) {
+    egui::Window::new("Networks")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-4.0, 4.0))
+        .show(contexts.ctx_mut(), |ui| {
+            for network in &config.networks {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} ({})", network.chain_name, network.chain_id));
+                    if game.chain == network.chain_id {
+                        ui.label("connected");
+                    } else if ui.button("Switch").clicked() {
+                        wallet.switch_chain(network);
+                    }
+                });
+            }
+        });
+}
@@ -1,23 +1,317 @@
 use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use ethers_core::{
+    abi::{decode, parse_abi, Abi, ParamType, Token},
+    types::{Address, U256},
+    utils::format_units,
+};
+use serde_json::{json, Value};
 
-use crate::{Game, GameState};
+use crate::{
+    rpc::{result_bytes, ChainRpc},
+    Game, GameState,
+};
+
+const ABI: &[&str] = &[
+    "function roomInfo(uint256 roomId) view returns (address[], address, address, uint256, uint8)",
+    "function token() view returns (address)",
+    "function decimals() view returns (uint8)",
+    "event ClaimRoom(uint256 room)",
+    "event Ranking(address player, uint256 win, uint256 reward)",
+];
+
+/// blocks before the game over to search the settlement events
+const LOG_BLOCKS: u64 = 1000;
+
+/// the room settlement on chain, the contract claims the room with the result
+#[derive(Debug, PartialEq, Eq)]
+pub enum Status {
+    Loading,
+    // the sequencer has not submitted the result
+    WaitingProof,
+    Settled,
+}
+
+#[derive(Resource)]
+pub struct Settlement {
+    abi: Abi,
+    rpc: ChainRpc,
+    status: Status,
+    // first block of the events
+    from_block: Option<u64>,
+    // the win and reward of the account when settled
+    reward: Option<(U256, U256)>,
+    // of the reward token
+    decimals: Option<u32>,
+}
+
+impl Settlement {
+    fn new(game: &Game) -> Self {
+        let abi = parse_abi(ABI).expect("invalid over abi");
+        let rpc = ChainRpc::default();
+        rpc.request(&game.network.rpc, "block", "eth_blockNumber", json!([]));
+        let data = abi
+            .function("token")
+            .and_then(|f| f.encode_input(&[]))
+            .unwrap_or_default();
+        rpc.call(&game.network.rpc, "token", &game.network.contract, &data);
+        Self {
+            abi,
+            rpc,
+            status: Status::Loading,
+            from_block: None,
+            reward: None,
+            decimals: None,
+        }
+    }
+
+    fn poll(&self, game: &Game) {
+        let url = &game.network.rpc;
+        if self.status != Status::Settled {
+            let data = self
+                .abi
+                .function("roomInfo")
+                .and_then(|f| f.encode_input(&[Token::Uint(game.room.into())]))
+                .unwrap_or_default();
+            self.rpc
+                .call(url, "roomInfo", &game.network.contract, &data);
+        }
+
+        if let (Some(from), None) = (self.from_block, self.reward) {
+            let topics: Vec<String> = ["ClaimRoom", "Ranking"]
+                .iter()
+                .filter_map(|name| self.abi.event(name).ok())
+                .map(|event| format!("{:?}", event.signature()))
+                .collect();
+            let filter = json!({
+                "address": game.network.contract,
+                "fromBlock": format!("0x{:x}", from),
+                "toBlock": "latest",
+                "topics": [topics],
+            });
+            self.rpc
+                .request(url, "logs", "eth_getLogs", json!([filter]));
+        }
+    }
+
+    fn receive(&mut self, game: &Game) {
+        while let Some((id, result)) = self.rpc.recv() {
+            let result = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    warn!("Settlement {}: {}", id, err);
+                    continue;
+                }
+            };
+            match id.as_str() {
+                "block" => {
+                    let block = result
+                        .as_str()
+                        .and_then(|b| u64::from_str_radix(b.trim_start_matches("0x"), 16).ok())
+                        .unwrap_or(0);
+                    self.from_block = Some(block.saturating_sub(LOG_BLOCKS));
+                }
+                "roomInfo" => self.room_info(&result_bytes(&result)),
+                "logs" => self.logs(game, result),
+                "token" => {
+                    let Some(token) = self.output(&id, &result).and_then(|t| t.into_address())
+                    else {
+                        continue;
+                    };
+                    let data = self
+                        .abi
+                        .function("decimals")
+                        .and_then(|f| f.encode_input(&[]))
+                        .unwrap_or_default();
+                    self.rpc.call(
+                        &game.network.rpc,
+                        "decimals",
+                        &format!("{:?}", token),
+                        &data,
+                    );
+                }
+                "decimals" => {
+                    self.decimals = self
+                        .output(&id, &result)
+                        .and_then(|t| t.into_uint())
+                        .map(|v| v.as_u32())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// the first output of the call
+    fn output(&self, name: &str, result: &Value) -> Option<Token> {
+        self.abi
+            .function(name)
+            .ok()
+            .and_then(|f| f.decode_output(&result_bytes(result)).ok())
+            .and_then(|tokens| tokens.into_iter().next())
+    }
+
+    /// status: none, opening, waiting, playing, over
+    fn room_info(&mut self, output: &[u8]) {
+        let Some(status) = self
+            .abi
+            .function("roomInfo")
+            .ok()
+            .and_then(|f| f.decode_output(output).ok())
+            .and_then(|tokens| tokens.get(4).cloned())
+            .and_then(|t| t.into_uint())
+        else {
+            return;
+        };
+        self.status = match status.as_u64() {
+            3 => Status::WaitingProof,
+            // deleted when claimed
+            0 if self.status != Status::Loading => Status::Settled,
+            _ => return,
+        };
+    }
+
+    /// the ranking events in the claim transaction of the room
+    fn logs(&mut self, game: &Game, logs: Value) {
+        let (Ok(claim), Ok(ranking)) = (self.abi.event("ClaimRoom"), self.abi.event("Ranking"))
+        else {
+            return;
+        };
+        let topic = |log: &Value, signature| {
+            log["topics"][0].as_str().map(|t| t.to_lowercase()) == Some(format!("{:?}", signature))
+        };
+        let logs = logs.as_array().cloned().unwrap_or_default();
+
+        let Some(claimed) = logs.iter().find(|log| {
+            topic(log, claim.signature())
+                && decode(&[ParamType::Uint(256)], &result_bytes(&log["data"]))
+                    .ok()
+                    .and_then(|t| t[0].clone().into_uint())
+                    == Some(game.room.into())
+        }) else {
+            return;
+        };
+        self.status = Status::Settled;
+
+        let account = game.account.parse::<Address>().ok();
+        let mut reward = (U256::zero(), U256::zero());
+        let types = [
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+        ];
+        for log in &logs {
+            if log["transactionHash"] != claimed["transactionHash"]
+                || !topic(log, ranking.signature())
+            {
+                continue;
+            }
+            let Ok(tokens) = decode(&types, &result_bytes(&log["data"])) else {
+                continue;
+            };
+            if tokens[0].clone().into_address() == account {
+                reward.0 += tokens[1].clone().into_uint().unwrap_or_default();
+                reward.1 += tokens[2].clone().into_uint().unwrap_or_default();
+            }
+        }
+        self.reward = Some(reward);
+    }
+}
 
 // restart the game when pressing spacebar
 pub fn gameover_keyboard(
+    game: Res<Game>,
     mut next_state: ResMut<NextState<GameState>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
 ) {
     if keyboard_input.just_pressed(KeyCode::Space) {
-        next_state.set(GameState::Listing);
+        next_state.set(game.back_state());
     }
 }
 
-// display the number of cake eaten before losing
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const YOU_COLOR: Color = Color::rgb(1.0, 0.8, 0.3);
+const MISMATCH_COLOR: Color = Color::rgb(1.0, 0.3, 0.3);
+
+/// the rows of standings, or the local scores from the old sequencer
+fn standings_lines(game: &Game) -> Vec<(String, Color)> {
+    let mut lines = vec![];
+    let players: Vec<(usize, String, u32, bool)> = match &game.standings {
+        Some(standings) => standings.players.clone(),
+        None => {
+            let mut scores: Vec<(&String, &u32)> = game.scores.iter().collect();
+            scores.sort_by(|(_, a), (_, b)| b.cmp(a));
+            let mut rank = 0;
+            scores
+                .into_iter()
+                .map(|(account, score)| {
+                    let bot = game.bots.contains(account);
+                    if !bot {
+                        rank += 1;
+                    }
+                    (if bot { 0 } else { rank }, account.clone(), *score, bot)
+                })
+                .collect()
+        }
+    };
+
+    for (rank, account, score, bot) in players {
+        let rank = if rank == 0 {
+            "-".to_owned()
+        } else {
+            format!("#{}", rank)
+        };
+        let is_you = account.eq_ignore_ascii_case(&game.account);
+        let mark = if is_you {
+            " (you)"
+        } else if bot {
+            " (bot)"
+        } else {
+            ""
+        };
+        lines.push((
+            format!("{} {} {} cakes{}", rank, account, score, mark),
+            if is_you { YOU_COLOR } else { TEXT_COLOR },
+        ));
+    }
+
+    match &game.standings {
+        Some(standings) => {
+            lines.push((format!("Cakes missed: {}", standings.missed), TEXT_COLOR));
+            lines.push((
+                format!(
+                    "Duration: {}:{:02}",
+                    standings.seconds / 60,
+                    standings.seconds % 60
+                ),
+                TEXT_COLOR,
+            ));
+            let proof = if standings.proof.is_empty() {
+                "Result: not proven".to_owned()
+            } else {
+                format!("Result: {} proof pending", standings.proof)
+            };
+            lines.push((proof, TEXT_COLOR));
+        }
+        None => lines.push(("No standings from the sequencer".to_owned(), TEXT_COLOR)),
+    }
+    match &game.heads.mismatch {
+        Some(mismatch) => lines.push((mismatch.clone(), MISMATCH_COLOR)),
+        None if game.standings.as_ref().is_some_and(|s| s.head.is_some()) => {
+            lines.push(("Operations head: matched".to_owned(), TEXT_COLOR))
+        }
+        None => {}
+    }
+    lines
+}
+
+/// the final standings of all players
 pub fn display_score(mut commands: Commands, game: Res<Game>) {
     commands
         .spawn(NodeBundle {
             style: Style {
                 width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
                 ..default()
@@ -26,12 +320,71 @@ pub fn display_score(mut commands: Commands, game: Res<Game>) {
         })
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
-                format!("Cake eaten: {}", game.scores[&game.account]),
+                "Game Over",
                 TextStyle {
                     font_size: 80.0,
                     color: Color::rgb(0.5, 0.5, 1.0),
                     ..default()
                 },
             ));
+
+            for (line, color) in standings_lines(&game) {
+                parent.spawn(TextBundle::from_section(
+                    line,
+                    TextStyle {
+                        font_size: 30.0,
+                        color,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+pub fn setup_settlement(mut commands: Commands, game: Res<Game>) {
+    // spectators have no reward
+    if !game.practice && !game.spectating {
+        commands.insert_resource(Settlement::new(&game));
+    }
+}
+
+pub fn cleanup(mut commands: Commands) {
+    commands.remove_resource::<Settlement>();
+}
+
+pub fn poll_settlement(game: Res<Game>, settlement: Res<Settlement>) {
+    settlement.poll(&game);
+}
+
+/// the settlement status and the reward
+pub fn show_settlement(
+    mut contexts: EguiContexts,
+    game: Res<Game>,
+    mut settlement: ResMut<Settlement>,
+) {
+    settlement.receive(&game);
+
+    egui::Window::new(format!("Room {}", game.room))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+        .show(contexts.ctx_mut(), |ui| {
+            match settlement.status {
+                Status::Loading => ui.label("Loading the room..."),
+                Status::WaitingProof => ui.label("Waiting for the proof of sequencer..."),
+                Status::Settled => ui.label("Room is settled"),
+            };
+
+            match (settlement.reward, settlement.decimals) {
+                (Some((win, reward)), Some(decimals)) if !reward.is_zero() || !win.is_zero() => {
+                    let reward = format_units(reward, decimals).unwrap_or_default();
+                    ui.label(format!("Reward: {} (win +{})", reward, win));
+                }
+                (Some((win, reward)), _) if reward.is_zero() && win.is_zero() => {
+                    ui.label("No reward in this room");
+                }
+                _ if settlement.status == Status::Settled => {
+                    ui.label("Loading the reward...");
+                }
+                _ => {}
+            }
         });
 }
@@ -1,6 +1,12 @@
+use aca_core::move_message;
 use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
 use serde::Deserialize;
-use std::f32::consts::PI;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    f32::consts::PI,
+};
 use z4_bevy::{build_request, parse_response, RecvError};
 
 #[cfg(target_arch = "wasm32")]
@@ -10,7 +16,10 @@ use z4_bevy::wasm::{ws_connect, WsConnection};
 use z4_bevy::ws::{ws_connect, WsConnection};
 
 use crate::{
+    practice::Practice,
+    storage::LAST_SERVER,
     style::{BOARD_SIZE_I, BOARD_SIZE_J, RESET_FOCUS},
+    tween::{move_to, Alien},
     Game, GameState,
 };
 
@@ -30,7 +39,221 @@ pub struct Cake {
     entity: Entity,
 }
 
-pub fn setup(mut commands: Commands, mut game: ResMut<Game>) {
+/// min seconds between moves, the sequencer rejects the moves in 300ms
+const MOVE_INTERVAL: f32 = 0.35;
+/// seconds to wait for the echo of a move, or it is rejected
+const MOVE_EXPIRE: f32 = 1.5;
+
+/// The moves predicted locally and not confirmed by the sequencer, the alien
+/// moves at once and snaps back when the sequencer rejects or corrects.
+#[derive(Default, Debug)]
+pub struct Prediction {
+    // the last position confirmed by the sequencer
+    confirmed: (usize, usize),
+    // sequence, position and the sent time of the pending moves
+    pending: VecDeque<(u64, (usize, usize), f32)>,
+    last_sent: Option<f32>,
+}
+
+impl Prediction {
+    fn reset(&mut self, position: (usize, usize)) {
+        *self = Self {
+            confirmed: position,
+            ..default()
+        };
+    }
+
+    fn can_move(&self, now: f32) -> bool {
+        match self.last_sent {
+            Some(last) => now - last >= MOVE_INTERVAL,
+            None => true,
+        }
+    }
+
+    fn predict(&mut self, seq: u64, position: (usize, usize), now: f32) {
+        self.pending.push_back((seq, position, now));
+        self.last_sent = Some(now);
+    }
+
+    /// the echo of own move, without the sequence from the old sequencers.
+    /// Return the position to show: the latest pending or the confirmed.
+    fn confirm(&mut self, seq: Option<u64>, position: (usize, usize)) -> (usize, usize) {
+        self.confirmed = position;
+        match seq {
+            Some(seq) => self.pending.retain(|(s, _, _)| *s > seq),
+            None => {
+                self.pending.pop_front();
+            }
+        }
+        self.pending.back().map_or(position, |(_, p, _)| *p)
+    }
+
+    /// drop the pending moves when one is rejected, return the confirmed
+    /// position to snap back
+    fn reject(&mut self, seq: u64) -> Option<(usize, usize)> {
+        if !self.pending.iter().any(|(s, _, _)| *s == seq) {
+            return None;
+        }
+        self.pending.clear();
+        Some(self.confirmed)
+    }
+
+    /// drop the pending moves when the oldest is not confirmed in time,
+    /// return the confirmed position to snap back
+    fn expire(&mut self, now: f32) -> Option<(usize, usize)> {
+        let (_, _, sent) = self.pending.front()?;
+        if now - sent < MOVE_EXPIRE {
+            return None;
+        }
+        self.pending.clear();
+        Some(self.confirmed)
+    }
+}
+
+/// the transform on the cell, facing the move direction
+fn move_transform(board: &[Vec<Cell>], old: (usize, usize), new: (usize, usize)) -> Transform {
+    let mut rotation = 0.0;
+    if old.0 > new.0 {
+        rotation = -PI / 2.;
+    }
+    if old.0 < new.0 {
+        rotation = PI / 2.;
+    }
+    if old.1 < new.1 {
+        rotation = PI;
+    }
+    if old.1 > new.1 {
+        rotation = 0.0;
+    }
+
+    Transform {
+        translation: Vec3::new(new.0 as f32, board[new.0][new.1].height, new.1 as f32),
+        rotation: Quat::from_rotation_y(rotation),
+        ..default()
+    }
+}
+
+/// move own alien to the position
+fn place_player(
+    commands: &mut Commands,
+    game: &mut Game,
+    transforms: &Query<&mut Transform>,
+    position: (usize, usize),
+) {
+    let old = (game.player.i, game.player.j);
+    if old == position {
+        return;
+    }
+    game.player.i = position.0;
+    game.player.j = position.1;
+    if let Some(entity) = game.player.entity {
+        let transform = move_transform(&game.board, old, position);
+        move_to(commands, transforms, entity, transform);
+    }
+}
+
+/// The operations hash chain heads from the sequencer, checked when they
+/// arrive and against the settled head of the result when over.
+#[derive(Default, Debug)]
+pub struct Heads {
+    // operations count -> head
+    seen: BTreeMap<u64, String>,
+    // the first mismatch, shown when over
+    pub mismatch: Option<String>,
+}
+
+impl Heads {
+    fn fail(&mut self, mismatch: String) {
+        warn!("{}", mismatch);
+        if self.mismatch.is_none() {
+            self.mismatch = Some(mismatch);
+        }
+    }
+
+    /// the latest operations count
+    fn last(&self) -> u64 {
+        self.seen.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// the chain only grows, another head at a seen count or a lower count
+    /// means history was rewritten
+    fn record(&mut self, count: u64, head: String) {
+        match self.seen.get(&count) {
+            Some(seen) if *seen != head => {
+                let mismatch = format!("Operations head {} rewritten: {} -> {}", count, seen, head);
+                self.fail(mismatch);
+            }
+            None if self.last() > count => {
+                let mismatch = format!("Operations head rewritten back to {}: {}", count, head);
+                self.fail(mismatch);
+            }
+            _ => {}
+        }
+        self.seen.insert(count, head);
+    }
+
+    /// the settled result must end the same chain
+    fn settle(&mut self, count: u64, head: &str) {
+        match self.seen.get(&count) {
+            Some(seen) if seen != head => {
+                let mismatch = format!("Settled head {} is not the seen {}", head, seen);
+                self.fail(mismatch);
+            }
+            None if self.last() > count => {
+                let mismatch = format!("Settled {} operations, fewer than the seen", count);
+                self.fail(mismatch);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The final standings from the sequencer `over`.
+#[derive(Debug)]
+pub struct Standings {
+    // rank (0 for bots), account, score, bot
+    pub players: Vec<(usize, String, u32, bool)>,
+    pub missed: u32,
+    pub seconds: u64,
+    // proof system of the result, still proving when over, empty when not proven
+    pub proof: String,
+    // operations count and head of the result
+    pub head: Option<(u64, String)>,
+}
+
+impl Standings {
+    /// [standings, missed, seconds, proof, count, head], none from the old sequencer
+    fn parse(params: &[Value]) -> Option<Self> {
+        let players = serde_json::from_value(params.first()?.clone()).ok()?;
+        let head = params
+            .get(4)
+            .and_then(|v| v.as_u64())
+            .zip(params.get(5).and_then(|v| v.as_str()))
+            .map(|(count, head)| (count, head.to_owned()));
+        Some(Self {
+            players,
+            missed: params.get(1)?.as_u64()? as u32,
+            seconds: params.get(2)?.as_u64()?,
+            proof: params.get(3)?.as_str()?.to_owned(),
+            head,
+        })
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut game: ResMut<Game>,
+    mut stats: ResMut<QueueStats>,
+    mut queue: ResMut<MessageQueue>,
+) {
+    game.standings = None;
+    game.heads = Heads::default();
+    game.follow = None;
+    queue.0.clear();
+    *stats = QueueStats {
+        overlay: stats.overlay,
+        ..default()
+    };
     game.camera_should_focus = Vec3::from(RESET_FOCUS);
     game.camera_is_focus = game.camera_should_focus;
     commands.spawn(Camera3dBundle {
@@ -43,15 +266,22 @@ pub fn setup(mut commands: Commands, mut game: ResMut<Game>) {
         ..default()
     });
 
-    ws_connect(&mut commands, &game.server, &game.peer, game.room);
+    // practice messages are from the local rules
+    if !game.practice {
+        let server = game.server.clone();
+        game.storage.set(LAST_SERVER, &server);
+        ws_connect(&mut commands, &game.server, &game.peer, game.room);
+    }
 }
 
 pub fn cleanup(mut game: ResMut<Game>) {
+    game.move_seq = 0;
+    game.prediction = Prediction::default();
     game.cakes.clear();
     game.cake_last = None;
 }
 
-pub fn play_setup(mut commands: Commands, mut game: ResMut<Game>, asset_server: Res<AssetServer>) {
+pub fn play_setup(commands: &mut Commands, game: &mut Game, asset_server: &AssetServer) {
     commands.spawn(PointLightBundle {
         transform: Transform::from_xyz(4.0, 10.0, 4.0),
         point_light: PointLight {
@@ -76,24 +306,27 @@ pub fn play_setup(mut commands: Commands, mut game: ResMut<Game>, asset_server:
         }
     }
 
-    // spawn the game character
-    game.player.entity = Some(
+    // spawn the game character, spectators have none
+    game.player.entity = (!game.spectating).then(|| {
         commands
-            .spawn(SceneBundle {
-                transform: Transform {
-                    translation: Vec3::new(
-                        game.player.i as f32,
-                        game.board[game.player.i][game.player.j].height,
-                        game.player.j as f32,
-                    ),
-                    rotation: Quat::from_rotation_y(-PI / 2.),
+            .spawn((
+                SceneBundle {
+                    transform: Transform {
+                        translation: Vec3::new(
+                            game.player.i as f32,
+                            game.board[game.player.i][game.player.j].height,
+                            game.player.j as f32,
+                        ),
+                        rotation: Quat::from_rotation_y(-PI / 2.),
+                        ..default()
+                    },
+                    scene: asset_server.load("models/AlienCake/alien.glb#Scene0"),
                     ..default()
                 },
-                scene: asset_server.load("models/AlienCake/alien.glb#Scene0"),
-                ..default()
-            })
-            .id(),
-    );
+                Alien::default(),
+            ))
+            .id()
+    });
 
     let mut entities = vec![];
     for (i, opponent) in game.opponents.iter() {
@@ -101,19 +334,22 @@ pub fn play_setup(mut commands: Commands, mut game: ResMut<Game>, asset_server:
             i.to_owned(),
             Some(
                 commands
-                    .spawn(SceneBundle {
-                        transform: Transform {
-                            translation: Vec3::new(
-                                opponent.i as f32,
-                                game.board[opponent.i][opponent.j].height,
-                                opponent.j as f32,
-                            ),
-                            rotation: Quat::from_rotation_y(-PI / 2.),
+                    .spawn((
+                        SceneBundle {
+                            transform: Transform {
+                                translation: Vec3::new(
+                                    opponent.i as f32,
+                                    game.board[opponent.i][opponent.j].height,
+                                    opponent.j as f32,
+                                ),
+                                rotation: Quat::from_rotation_y(-PI / 2.),
+                                ..default()
+                            },
+                            scene: asset_server.load("models/AlienCake/alien.glb#Scene0"),
                             ..default()
                         },
-                        scene: asset_server.load("models/AlienCake/alien.glb#Scene0"),
-                        ..default()
-                    })
+                        Alien::default(),
+                    ))
                     .id(),
             ),
         ));
@@ -145,12 +381,31 @@ pub fn play_setup(mut commands: Commands, mut game: ResMut<Game>, asset_server:
     );
 }
 
-// control the game character
+// control the game character, the move is shown at once and reconciled by the echo
 pub fn move_player(
+    mut commands: Commands,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    game: ResMut<Game>,
+    time: Res<Time>,
+    mut game: ResMut<Game>,
+    practice: Option<ResMut<Practice>>,
     connections: Query<(Entity, &WsConnection)>,
+    transforms: Query<&mut Transform>,
 ) {
+    // spectators are read-only
+    if game.spectating {
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    if let Some(confirmed) = game.prediction.expire(now) {
+        warn!("Move rejected, back to {:?}", confirmed);
+        place_player(&mut commands, &mut game, &transforms, confirmed);
+    }
+    // at the rate of the sequencer
+    if game.player.entity.is_none() || !game.prediction.can_move(now) {
+        return;
+    }
+
     let mut new_i = game.player.i;
     let mut new_j = game.player.j;
 
@@ -180,14 +435,36 @@ pub fn move_player(
         }
     }
 
-    if moved {
-        if let Ok((_, conn)) = connections.get_single() {
+    let connection = connections.get_single().ok();
+    if moved && (connection.is_some() || practice.is_some()) {
+        // sign the move, so sequencer cannot fabricate moves for us
+        let seq = game.move_seq + 1;
+        let message = move_message(game.room, seq, new_i, new_j);
+        let signature = match game.peer.sign(&message) {
+            Ok(sig) => sig.to_bytes().to_vec(),
+            Err(err) => {
+                error!("Sign move: {:?}", err);
+                return;
+            }
+        };
+        game.move_seq = seq;
+        game.prediction.predict(seq, (new_i, new_j), now);
+        place_player(&mut commands, &mut game, &transforms, (new_i, new_j));
+
+        if let Some((_, conn)) = connection {
             conn.send(build_request(
                 "move",
-                vec![new_i.into(), new_j.into()],
+                vec![
+                    new_i.into(),
+                    new_j.into(),
+                    seq.into(),
+                    hex::encode(signature).into(),
+                ],
                 &game.peer,
                 game.room,
             ));
+        } else if let Some(mut practice) = practice {
+            practice.moved(now, new_i, new_j, seq, signature);
         }
     }
 }
@@ -199,8 +476,17 @@ pub fn focus_camera(
     mut transforms: ParamSet<(Query<&mut Transform, With<Camera3d>>, Query<&Transform>)>,
 ) {
     const SPEED: f32 = 2.0;
+    // spectators follow the chosen player
+    let target = if game.spectating {
+        game.follow
+            .as_ref()
+            .and_then(|follow| game.opponents.get(follow))
+            .and_then(|p| p.entity)
+    } else {
+        game.player.entity
+    };
     // if there is both a player and a cake, target the mid-point of them
-    if let (Some(player_entity), Some(cake_entity)) = (game.player.entity, game.cake_last) {
+    if let (Some(player_entity), Some(cake_entity)) = (target, game.cake_last) {
         let transform_query = transforms.p1();
         if let (Ok(player_transform), Ok(cake_transform)) = (
             transform_query.get(player_entity),
@@ -211,7 +497,7 @@ pub fn focus_camera(
                 .lerp(cake_transform.translation, 0.5);
         }
         // otherwise, if there is only a player, target the player
-    } else if let Some(player_entity) = game.player.entity {
+    } else if let Some(player_entity) = target {
         if let Ok(player_transform) = transforms.p1().get(player_entity) {
             game.camera_should_focus = player_transform.translation;
         }
@@ -245,17 +531,58 @@ pub fn rotate_cake(game: Res<Game>, time: Res<Time>, mut transforms: Query<&mut
     }
 }
 
+/// the short account, e.g. `0xf3...2266`, or the whole when not an address
+fn short_account(account: &str) -> String {
+    match (account.get(0..4), account.get(38..)) {
+        (Some(head), Some(tail)) => format!("{}...{}", head, tail),
+        _ => account.to_owned(),
+    }
+}
+
 // update the score displayed during the game
 pub fn scoreboard_system(game: Res<Game>, mut query: Query<&mut Text>) {
     for mut text in &mut query {
         let mut string = String::from("Score: ");
         for (p, s) in &game.scores {
-            string += &format!("{}...{}:{} ", &p[0..4], &p[40..], s);
+            if game.bots.contains(p) {
+                string += &format!("{}(bot):{} ", short_account(p), s);
+            } else {
+                string += &format!("{}:{} ", short_account(p), s);
+            }
         }
         text.sections[0].value = string;
     }
 }
 
+/// choose the player followed by the camera when spectating
+pub fn spectator(mut contexts: EguiContexts, mut game: ResMut<Game>) {
+    if !game.spectating {
+        return;
+    }
+
+    let mut players: Vec<String> = game.opponents.keys().cloned().collect();
+    players.sort();
+    let mut follow = game.follow.clone();
+    egui::Window::new(format!("Spectating room {}", game.room))
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-4.0, 4.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Follow:");
+            for player in players {
+                let score = game.scores.get(&player).copied().unwrap_or(0);
+                let mark = if game.bots.contains(&player) {
+                    "(bot)"
+                } else {
+                    ""
+                };
+                let text = format!("{}{}: {}", short_account(&player), mark, score);
+                ui.radio_value(&mut follow, Some(player), text);
+            }
+        });
+    if follow != game.follow {
+        game.follow = follow;
+    }
+}
+
 /// player id, init position, init score
 #[derive(Deserialize, Default)]
 struct PlayerStatus(String, usize, usize, u32);
@@ -272,6 +599,38 @@ struct CakeAppear(u32, usize, usize);
 #[derive(Deserialize, Default)]
 struct CakeEaten(u32, String, u32);
 
+/// max messages handled in one frame, the rest are left to the next frames
+pub const MESSAGE_BUDGET: usize = 256;
+
+/// The messages received from the connection and not handled yet.
+#[derive(Resource, Default)]
+pub struct MessageQueue(VecDeque<String>);
+
+/// The message queue depth of the frames, shown in the debug overlay.
+#[derive(Resource, Default)]
+pub struct QueueStats {
+    // messages queued in the last frame, and some are left to the next frame
+    depth: usize,
+    behind: bool,
+    peak: usize,
+    // frames left messages to the next frame
+    lagging: u64,
+    overlay: bool,
+}
+
+impl QueueStats {
+    pub fn record(&mut self, depth: usize, behind: bool) {
+        self.depth = depth;
+        self.behind = behind;
+        self.peak = self.peak.max(depth);
+        if behind {
+            self.lagging += 1;
+        }
+    }
+}
+
+/// drain the connection to the queue, and handle the queued messages in order,
+/// at most the budget in one frame
 pub fn ws_message(
     mut commands: Commands,
     mut game: ResMut<Game>,
@@ -279,168 +638,274 @@ pub fn ws_message(
     asset_server: Res<AssetServer>,
     mut transforms: Query<&mut Transform>,
     connections: Query<(Entity, &WsConnection)>,
+    mut queue: ResMut<MessageQueue>,
+    mut stats: ResMut<QueueStats>,
 ) {
     if let Ok((entity, conn)) = connections.get_single() {
-        match conn.recv() {
-            Ok(message) => match parse_response(&message) {
-                Ok((_room, method, mut params)) => match method.as_str() {
-                    "connected" => {
-                        // setup game board
-                        let boardv = params.pop().unwrap_or(Default::default());
-                        let boards: Vec<Cell> = boardv
-                            .as_str()
-                            .unwrap_or("")
-                            .split(",")
-                            .map(|v| Cell {
-                                height: v.parse::<f32>().unwrap_or(0.0),
-                            })
-                            .collect();
-
-                        game.board.clear();
-                        for j in boards.chunks(BOARD_SIZE_J) {
-                            game.board.push(j.to_vec());
-                        }
-
-                        // setup game players & scores
-                        game.opponents.clear();
-                        game.scores.clear();
-                        for param in params {
-                            let ps =
-                                serde_json::from_value(param).unwrap_or(PlayerStatus::default());
-                            if ps.0 == game.account {
-                                game.player.i = ps.1;
-                                game.player.j = ps.2;
-                            } else {
-                                let mut player = Player::default();
-                                player.i = ps.1;
-                                player.j = ps.2;
-                                game.opponents.insert(ps.0.clone(), player);
-                            }
-
-                            game.scores.insert(ps.0, ps.3);
-                        }
-                        play_setup(commands, game, asset_server);
-                    }
-                    "moved" => {
-                        if params.len() != 3 || game.player.entity.is_none() {
-                            return;
-                        }
-                        let player = params[0].as_str().unwrap_or("");
-                        let new_i = params[1].as_u64().unwrap_or(0) as usize;
-                        let new_j = params[2].as_u64().unwrap_or(0) as usize;
-
-                        // Set new position
-                        let (old_i, old_j, entity) = if player == &game.account {
-                            let old = (game.player.i, game.player.j);
-                            game.player.i = new_i;
-                            game.player.j = new_j;
-                            (old.0, old.1, game.player.entity.unwrap())
-                        } else {
-                            if let Some(p) = game.opponents.get_mut(player) {
-                                let old = (p.i, p.j);
-                                p.i = new_i;
-                                p.j = new_j;
-                                (old.0, old.1, p.entity.unwrap())
-                            } else {
-                                return;
-                            }
-                        };
-
-                        // move rotation
-                        let mut rotation = 0.0;
-                        if old_i > new_i {
-                            rotation = -PI / 2.;
-                        }
-                        if old_i < new_i {
-                            rotation = PI / 2.;
-                        }
-                        if old_j < new_j {
-                            rotation = PI;
-                        }
-                        if old_j > new_j {
-                            rotation = 0.0;
-                        }
-
-                        // move transforms
-                        *transforms.get_mut(entity).unwrap() = Transform {
-                            translation: Vec3::new(
-                                new_i as f32,
-                                game.board[new_i][new_j].height,
-                                new_j as f32,
-                            ),
-                            rotation: Quat::from_rotation_y(rotation),
+        loop {
+            match conn.recv() {
+                Ok(message) => queue.0.push_back(message),
+                Err(RecvError::Empty) => break,
+                Err(RecvError::Closed) => {
+                    commands.entity(entity).despawn();
+                    break;
+                }
+            }
+        }
+    }
+
+    let depth = queue.0.len();
+    for _ in 0..MESSAGE_BUDGET {
+        let Some(message) = queue.0.pop_front() else {
+            break;
+        };
+        match parse_response(&message) {
+            Ok((_room, method, params)) => {
+                handle_message(
+                    &mut commands,
+                    &mut game,
+                    &mut next_state,
+                    &asset_server,
+                    &mut transforms,
+                    &method,
+                    params,
+                );
+                // the rest are after the game
+                if method == "over" {
+                    break;
+                }
+            }
+            Err(err) => error!("WS: {}, message: {}", err, message),
+        }
+    }
+    stats.record(depth, !queue.0.is_empty());
+}
+
+/// toggle the debug overlay by F3
+pub fn debug_overlay(
+    mut contexts: EguiContexts,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut stats: ResMut<QueueStats>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        stats.overlay = !stats.overlay;
+    }
+    if !stats.overlay {
+        return;
+    }
+
+    egui::Area::new("DEBUG")
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(4.0, -4.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!(
+                "Queue: {}{} (peak {}), budget {}/frame, lagging frames {}",
+                stats.depth,
+                if stats.behind { "+" } else { "" },
+                stats.peak,
+                MESSAGE_BUDGET,
+                stats.lagging
+            ));
+        });
+}
+
+/// handle the game message, from the sequencer or the local practice
+pub fn handle_message(
+    commands: &mut Commands,
+    game: &mut Game,
+    next_state: &mut NextState<GameState>,
+    asset_server: &AssetServer,
+    transforms: &mut Query<&mut Transform>,
+    method: &str,
+    mut params: Vec<Value>,
+) {
+    match method {
+        "connected" => {
+            // setup game board
+            let boardv = params.pop().unwrap_or(Default::default());
+            let boards: Vec<Cell> = boardv
+                .as_str()
+                .unwrap_or("")
+                .split(",")
+                .map(|v| Cell {
+                    height: v.parse::<f32>().unwrap_or(0.0),
+                })
+                .collect();
+
+            game.board.clear();
+            for j in boards.chunks(BOARD_SIZE_J) {
+                game.board.push(j.to_vec());
+            }
+
+            // setup game players & scores
+            game.opponents.clear();
+            game.scores.clear();
+            game.bots.clear();
+            for mut param in params {
+                // the 5th status is the bot flag
+                let mut is_bot = false;
+                if let Some(status) = param.as_array_mut() {
+                    is_bot = status.get(4).and_then(|v| v.as_bool()).unwrap_or(false);
+                    status.truncate(4);
+                }
+                let ps = serde_json::from_value(param).unwrap_or(PlayerStatus::default());
+                if is_bot {
+                    game.bots.insert(ps.0.clone());
+                }
+                if !game.spectating && ps.0 == game.account {
+                    game.player.i = ps.1;
+                    game.player.j = ps.2;
+                    game.prediction.reset((ps.1, ps.2));
+                } else {
+                    let mut player = Player::default();
+                    player.i = ps.1;
+                    player.j = ps.2;
+                    game.opponents.insert(ps.0.clone(), player);
+                }
+
+                game.scores.insert(ps.0, ps.3);
+            }
+            if game.spectating {
+                // follow the first player
+                let mut players: Vec<&String> = game
+                    .opponents
+                    .keys()
+                    .filter(|p| !game.bots.contains(*p))
+                    .collect();
+                players.sort();
+                game.follow = players.first().map(|p| p.to_string());
+            }
+            play_setup(commands, game, asset_server);
+        }
+        "moved" => {
+            if params.len() < 3 {
+                return;
+            }
+            let player = params[0].as_str().unwrap_or("");
+            let new_i = params[1].as_u64().unwrap_or(0) as usize;
+            let new_j = params[2].as_u64().unwrap_or(0) as usize;
+            // the move sequence, none from the old sequencers
+            let seq = params.get(3).and_then(|v| v.as_u64());
+
+            // reconcile own predicted moves
+            if !game.spectating && player == game.account {
+                let position = game.prediction.confirm(seq, (new_i, new_j));
+                place_player(commands, game, transforms, position);
+                return;
+            }
+
+            // Set new position
+            let Some(p) = game.opponents.get_mut(player) else {
+                return;
+            };
+            let old = (p.i, p.j);
+            p.i = new_i;
+            p.j = new_j;
+            // not setup yet
+            let Some(entity) = p.entity else {
+                return;
+            };
+
+            let transform = move_transform(&game.board, old, (new_i, new_j));
+            move_to(commands, transforms, entity, transform);
+        }
+        // own move refused, from the practice
+        "rejected" => {
+            let Some(seq) = params.first().and_then(|v| v.as_u64()) else {
+                return;
+            };
+            if let Some(position) = game.prediction.reject(seq) {
+                place_player(commands, game, transforms, position);
+            }
+        }
+        "cake" => {
+            if params.len() != 3 {
+                return;
+            }
+            let index = params[0].as_u64().unwrap_or(0) as u32;
+            let i = params[1].as_u64().unwrap_or(0) as usize;
+            let j = params[2].as_u64().unwrap_or(0) as usize;
+
+            let entity = commands
+                .spawn(SceneBundle {
+                    transform: Transform::from_xyz(
+                        i as f32,
+                        game.board[i][j].height + 0.2,
+                        j as f32,
+                    ),
+                    scene: game.cake_handle.clone(),
+                    ..default()
+                })
+                .with_children(|children| {
+                    children.spawn(PointLightBundle {
+                        point_light: PointLight {
+                            color: Color::rgb(1.0, 1.0, 0.0),
+                            intensity: 500_000.0,
+                            range: 10.0,
                             ..default()
-                        };
-                    }
-                    "cake" => {
-                        if params.len() != 3 {
-                            return;
-                        }
-                        let index = params[0].as_u64().unwrap_or(0) as u32;
-                        let i = params[1].as_u64().unwrap_or(0) as usize;
-                        let j = params[2].as_u64().unwrap_or(0) as usize;
-
-                        let entity = commands
-                            .spawn(SceneBundle {
-                                transform: Transform::from_xyz(
-                                    i as f32,
-                                    game.board[i][j].height + 0.2,
-                                    j as f32,
-                                ),
-                                scene: game.cake_handle.clone(),
-                                ..default()
-                            })
-                            .with_children(|children| {
-                                children.spawn(PointLightBundle {
-                                    point_light: PointLight {
-                                        color: Color::rgb(1.0, 1.0, 0.0),
-                                        intensity: 500_000.0,
-                                        range: 10.0,
-                                        ..default()
-                                    },
-                                    transform: Transform::from_xyz(0.0, 2.0, 0.0),
-                                    ..default()
-                                });
-                            })
-                            .id();
-
-                        game.cake_last = Some(entity);
-                        game.cakes.insert(index, Cake { entity });
-                    }
-                    "eaten" => {
-                        if params.len() != 3 {
-                            return;
-                        }
-                        let index = params[0].as_u64().unwrap_or(0) as u32;
-                        let player = params[1].as_str().unwrap_or("");
-                        let score = params[2].as_u64().unwrap_or(0) as u32;
-
-                        // clear cake
-                        if let Some(cake) = game.cakes.remove(&index) {
-                            commands.entity(cake.entity).despawn_recursive();
-                            if let Some(last) = game.cake_last {
-                                if last == cake.entity {
-                                    game.cake_last = None;
-                                }
-                            }
-                        }
-
-                        // update scores
-                        if !player.is_empty() {
-                            game.scores.get_mut(player).map(|val| {
-                                *val = score;
-                            });
-                        }
-                    }
-                    "over" => {
-                        next_state.set(GameState::GameOver);
+                        },
+                        transform: Transform::from_xyz(0.0, 2.0, 0.0),
+                        ..default()
+                    });
+                })
+                .id();
+
+            game.cake_last = Some(entity);
+            game.cakes.insert(index, Cake { entity });
+        }
+        "eaten" => {
+            if params.len() != 3 {
+                return;
+            }
+            let index = params[0].as_u64().unwrap_or(0) as u32;
+            let player = params[1].as_str().unwrap_or("");
+            let score = params[2].as_u64().unwrap_or(0) as u32;
+
+            // clear cake
+            if let Some(cake) = game.cakes.remove(&index) {
+                commands.entity(cake.entity).despawn_recursive();
+                if let Some(last) = game.cake_last {
+                    if last == cake.entity {
+                        game.cake_last = None;
                     }
-                    _ => {}
-                },
-                Err(err) => error!("WS: {}, message: {}", err, message),
-            },
-            Err(RecvError::Empty) => {}
-            Err(RecvError::Closed) => commands.entity(entity).despawn(),
+                }
+            }
+
+            // update scores
+            if !player.is_empty() {
+                game.scores.get_mut(player).map(|val| {
+                    *val = score;
+                });
+            }
+        }
+        "head" => {
+            if params.len() != 2 {
+                return;
+            }
+            let count = params[0].as_u64().unwrap_or(0);
+            let head = params[1].as_str().unwrap_or("").to_owned();
+            game.heads.record(count, head);
         }
+        "over" => {
+            game.standings = Standings::parse(&params);
+            if let Some((count, head)) = game.standings.as_ref().and_then(|s| s.head.clone()) {
+                game.heads.settle(count, &head);
+            }
+            next_state.set(GameState::GameOver);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_account_works() {
+        assert_eq!(
+            short_account("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"),
+            "0xf3...2266"
+        );
+        assert_eq!(short_account("bot"), "bot");
     }
 }
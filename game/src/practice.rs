@@ -0,0 +1,257 @@
+//! Practice alone without wallet and sequencer. The game rules run in the
+//! client, and send the same messages as the sequencer to `play.rs`.
+
+use aca_core::{standings, GameState as Rules, Operation, Spawner};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use ethers_core::types::Address;
+use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+
+use crate::{
+    play::{handle_message, QueueStats, MESSAGE_BUDGET},
+    Game, GameState,
+};
+
+/// the room of practice, not in the market
+pub const PRACTICE_ROOM: u64 = 0;
+
+// same as the sequencer
+const TIMEOUT: f32 = 0.3;
+const CAKE_TIMEOUT: f32 = 4.0;
+const CAKE_NUMBER: usize = 10;
+const INIT_CAKE_TIMEOUT: f32 = 10.0;
+const LOOP_CAKE_TIMEOUT: f32 = 5.0;
+
+#[derive(Resource)]
+pub struct Practice {
+    // the peer is also the account
+    account: Address,
+    rules: Rules,
+    // board and cakes from the seed, same as the sequencer
+    spawner: Spawner,
+    // operations count
+    count: usize,
+    // alive cakes in created order: index, created time
+    alive: Vec<(u32, f32)>,
+    created: usize,
+    // eaten and missed cakes
+    finished: usize,
+    missed: u32,
+    started: f32,
+    next_tick: f32,
+    last_move: f32,
+    over: bool,
+    // messages to play: method, params
+    messages: VecDeque<(String, Vec<Value>)>,
+}
+
+impl Practice {
+    pub fn new(account: Address, now: f32) -> Self {
+        let mut seed = [0u8; 32];
+        rand::thread_rng().fill(&mut seed);
+        let mut spawner = Spawner::new(seed);
+
+        let board: Vec<String> = spawner
+            .board()
+            .iter()
+            .flatten()
+            .map(|height| height.to_string())
+            .collect();
+        let mut messages = VecDeque::new();
+        messages.push_back((
+            "connected".to_owned(),
+            vec![
+                json!((format!("{:?}", account), 0, 0, 0)),
+                json!(board.join(",")),
+            ],
+        ));
+
+        Self {
+            account,
+            rules: Rules::new(PRACTICE_ROOM, &seed, 1),
+            spawner,
+            count: 0,
+            alive: vec![],
+            created: 0,
+            finished: 0,
+            missed: 0,
+            started: now,
+            next_tick: now + INIT_CAKE_TIMEOUT,
+            last_move: 0.0,
+            over: false,
+            messages,
+        }
+    }
+
+    fn send(&mut self, method: &str, params: Vec<Value>) {
+        self.messages.push_back((method.to_owned(), params));
+    }
+
+    /// apply the operation if valid, same as the prover
+    fn apply(&mut self, operation: Operation) -> Result<(), &'static str> {
+        let mut rules = self.rules.clone();
+        let players = [self.account];
+        rules.apply(PRACTICE_ROOM, &players, &players, &operation)?;
+        self.rules = rules;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// the same over payload as the sequencer, the practice is not proven
+    fn finish(&mut self, now: f32) {
+        self.finished += 1;
+        if self.finished == CAKE_NUMBER {
+            self.over = true;
+            let standings: Vec<Value> = standings(&[self.account], &self.rules.scores)
+                .into_iter()
+                .map(|(rank, player, score)| json!((rank, format!("{:?}", player), score, false)))
+                .collect();
+            let seconds = (now - self.started) as u64;
+            let params = vec![
+                standings.into(),
+                self.missed.into(),
+                seconds.into(),
+                "".into(),
+                self.count.into(),
+                format!("0x{}", hex::encode(self.rules.head)).into(),
+            ];
+            self.send("over", params);
+        }
+    }
+
+    /// the cake task of the sequencer: clear the missed cakes, and create a new one
+    pub fn tick(&mut self, now: f32) {
+        if self.over || now < self.next_tick {
+            return;
+        }
+        self.next_tick = now + LOOP_CAKE_TIMEOUT;
+
+        while let Some((index, created)) = self.alive.first().copied() {
+            if now - created < CAKE_TIMEOUT {
+                break;
+            }
+            self.alive.remove(0);
+            let _ = self.apply(Operation::CakeMissed(index));
+            self.send("eaten", vec![index.into(), "".into(), 0.into()]);
+            self.missed += 1;
+            self.finish(now);
+        }
+        if self.over {
+            return;
+        }
+
+        if self.created < CAKE_NUMBER {
+            self.created += 1;
+            let index = self.created as u32;
+            let (i, j) = self.spawner.cake();
+            self.alive.push((index, now));
+            let _ = self.apply(Operation::CakeCreated(index, i, j));
+            self.send("cake", vec![index.into(), i.into(), j.into()]);
+        }
+
+        let head = format!("0x{}", hex::encode(self.rules.head));
+        self.send("head", vec![self.count.into(), head.into()]);
+    }
+
+    /// the signed move of player, rejected if too fast or invalid,
+    /// so the prediction snaps back at once
+    pub fn moved(&mut self, now: f32, i: usize, j: usize, seq: u64, signature: Vec<u8>) {
+        if self.over {
+            return;
+        }
+        if now - self.last_move < TIMEOUT {
+            warn!("Practice move {} too fast", seq);
+            self.send("rejected", vec![seq.into()]);
+            return;
+        }
+        self.last_move = now;
+
+        let cakes = self.rules.cakes.clone();
+        let mut score = self.rules.scores[0];
+        if let Err(err) = self.apply(Operation::Move(self.account, i, j, seq, signature)) {
+            warn!("Practice move {} rejected: {}", seq, err);
+            self.send("rejected", vec![seq.into()]);
+            return;
+        }
+
+        let account = format!("{:?}", self.account);
+        self.send(
+            "moved",
+            vec![account.clone().into(), i.into(), j.into(), seq.into()],
+        );
+        for (index, _, _) in cakes {
+            if self.rules.cakes.iter().any(|(c, _, _)| *c == index) {
+                continue;
+            }
+            self.alive.retain(|(c, _)| *c != index);
+            score += 1;
+            self.send(
+                "eaten",
+                vec![index.into(), account.clone().into(), score.into()],
+            );
+            self.finish(now);
+        }
+    }
+}
+
+pub fn practice_button(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut game: ResMut<Game>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+) {
+    egui::Area::new("PRACTICE")
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -40.0))
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("PRACTICE").clicked() {
+                let account = Address::from(game.peer.peer_id().0);
+                game.practice = true;
+                game.spectating = false;
+                game.account = format!("{:?}", account);
+                game.room = PRACTICE_ROOM;
+                commands.insert_resource(Practice::new(account, time.elapsed_seconds()));
+                next_state.set(GameState::Playing);
+            }
+        });
+}
+
+pub fn tick(time: Res<Time>, mut practice: ResMut<Practice>) {
+    practice.tick(time.elapsed_seconds());
+}
+
+pub fn practice_message(
+    mut commands: Commands,
+    mut game: ResMut<Game>,
+    mut next_state: ResMut<NextState<GameState>>,
+    asset_server: Res<AssetServer>,
+    mut transforms: Query<&mut Transform>,
+    mut practice: ResMut<Practice>,
+    mut stats: ResMut<QueueStats>,
+) {
+    let depth = practice.messages.len();
+    for _ in 0..MESSAGE_BUDGET {
+        let Some((method, params)) = practice.messages.pop_front() else {
+            break;
+        };
+        handle_message(
+            &mut commands,
+            &mut game,
+            &mut next_state,
+            &asset_server,
+            &mut transforms,
+            &method,
+            params,
+        );
+        if method == "over" {
+            break;
+        }
+    }
+    stats.record(depth, !practice.messages.is_empty());
+}
+
+pub fn cleanup(mut commands: Commands) {
+    commands.remove_resource::<Practice>();
+}
@@ -0,0 +1,64 @@
+//! JSON-RPC requests to the chain of the network, by `ehttp` in web and native.
+//! The results are received in the later frames, with the request id.
+
+use serde_json::{json, Value};
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+
+pub type RpcResult = Result<Value, String>;
+
+pub struct ChainRpc {
+    sender: Sender<(String, RpcResult)>,
+    receiver: Mutex<Receiver<(String, RpcResult)>>,
+}
+
+impl Default for ChainRpc {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+impl ChainRpc {
+    pub fn request(&self, url: &str, id: &str, method: &str, params: Value) {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let (sender, id) = (self.sender.clone(), id.to_owned());
+
+        let request = match ehttp::Request::json(url, &body) {
+            Ok(request) => request,
+            Err(err) => {
+                let _ = sender.send((id, Err(err.to_string())));
+                return;
+            }
+        };
+        ehttp::fetch(request, move |response| {
+            let result = response
+                .and_then(|r| r.json::<Value>().map_err(|e| e.to_string()))
+                .and_then(|v| match v.get("error") {
+                    Some(error) => Err(error.to_string()),
+                    None => Ok(v["result"].clone()),
+                });
+            let _ = sender.send((id, result));
+        });
+    }
+
+    /// `eth_call` the contract at the latest block
+    pub fn call(&self, url: &str, id: &str, to: &str, data: &[u8]) {
+        let params = json!([{ "to": to, "data": format!("0x{}", hex::encode(data)) }, "latest"]);
+        self.request(url, id, "eth_call", params);
+    }
+
+    pub fn recv(&self) -> Option<(String, RpcResult)> {
+        self.receiver.lock().ok()?.try_recv().ok()
+    }
+}
+
+/// the bytes of the hex result
+pub fn result_bytes(result: &Value) -> Vec<u8> {
+    hex::decode(result.as_str().unwrap_or("").trim_start_matches("0x")).unwrap_or_default()
+}
@@ -0,0 +1,103 @@
+//! Key-value storage of the client: the peer key, settings and last server.
+//! Browser session storage in wasm, and a JSON file in native.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+pub const PEER_KEY: &str = "peer-key";
+pub const LAST_SERVER: &str = "server";
+
+#[cfg(target_arch = "wasm32")]
+pub struct Storage {
+    // none when out of the page
+    session: Option<web_sys::Storage>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage {
+    pub fn load() -> Self {
+        let session = web_sys::window().and_then(|w| w.session_storage().ok().flatten());
+        Self { session }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.session.as_ref()?.get(key).ok().flatten()
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        if let Some(session) = &self.session {
+            let _ = session.set(key, value);
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/alien-cake-addict`, or in `$HOME/.config`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn config_dir() -> PathBuf {
+    std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_default()
+        .join("alien-cake-addict")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Storage {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage {
+    /// `ACA_STORAGE` file, default `$XDG_CONFIG_HOME/alien-cake-addict/storage.json`
+    pub fn load() -> Self {
+        let path = std::env::var("ACA_STORAGE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| config_dir().join("storage.json"));
+
+        let values = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self { path, values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.values.get(key).cloned()
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_owned(), value.to_owned());
+
+        if let Some(dir) = self.path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Ok(bytes) = serde_json::to_vec_pretty(&self.values) {
+            if let Err(err) = write_private(&self.path, &bytes) {
+                bevy::log::warn!("Storage {:?}: {}", self.path, err);
+            }
+        }
+    }
+}
+
+/// the file has the peer private key, only the owner can read it on unix
+#[cfg(not(target_arch = "wasm32"))]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // the mode is only for the new file, the old one from before
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(bytes)
+}
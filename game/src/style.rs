@@ -1,4 +1,4 @@
-use bevy::prelude::Color;
+use bevy::prelude::{Color, Mut, Text};
 
 pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 pub const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
@@ -11,3 +11,10 @@ pub const RESET_FOCUS: [f32; 3] = [
     0.0,
     BOARD_SIZE_J as f32 / 2.0 - 0.5,
 ];
+
+/// update the text in place, the change is detected only when the value is different
+pub fn set_text(mut text: Mut<Text>, value: &str) {
+    if text.sections[0].value != value {
+        text.sections[0].value = value.to_owned();
+    }
+}
@@ -0,0 +1,134 @@
+//! Smooth moves of the aliens: the translation and rotation are tweened over
+//! the move interval, and the walk and idle clips of `alien.glb` are played
+//! when the model has them.
+
+use bevy::{gltf::Gltf, prelude::*};
+use std::time::Duration;
+
+/// seconds of a move, same as the sequencer move interval
+const TWEEN_SECONDS: f32 = 0.3;
+/// blend between the walk and idle clips
+const CLIP_TRANSITION: Duration = Duration::from_millis(150);
+
+/// An alien on the board, and the animation player in its scene.
+#[derive(Component, Default)]
+pub struct Alien {
+    animator: Option<Entity>,
+    // the playing clip, walk or idle
+    walking: Option<bool>,
+}
+
+/// The move from the transform to another.
+#[derive(Component)]
+pub struct Tween {
+    from: Transform,
+    to: Transform,
+    elapsed: f32,
+}
+
+/// The alien model with the animations.
+#[derive(Resource)]
+pub struct AlienModel {
+    gltf: Handle<Gltf>,
+}
+
+impl FromWorld for AlienModel {
+    fn from_world(world: &mut World) -> Self {
+        let gltf = world
+            .resource::<AssetServer>()
+            .load("models/AlienCake/alien.glb");
+        Self { gltf }
+    }
+}
+
+/// tween the entity to the transform, or place it when not spawned yet
+pub fn move_to(
+    commands: &mut Commands,
+    transforms: &Query<&mut Transform>,
+    entity: Entity,
+    to: Transform,
+) {
+    match transforms.get(entity) {
+        Ok(from) => {
+            commands.entity(entity).insert(Tween {
+                from: *from,
+                to,
+                elapsed: 0.0,
+            });
+        }
+        Err(_) => {
+            commands.entity(entity).insert(to);
+        }
+    }
+}
+
+pub fn animate(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tweens: Query<(Entity, &mut Transform, &mut Tween)>,
+) {
+    for (entity, mut transform, mut tween) in &mut tweens {
+        tween.elapsed += time.delta_seconds();
+        let t = (tween.elapsed / TWEEN_SECONDS).min(1.0);
+        // ease in and out
+        let s = t * t * (3.0 - 2.0 * t);
+        transform.translation = tween.from.translation.lerp(tween.to.translation, s);
+        transform.rotation = tween.from.rotation.slerp(tween.to.rotation, s);
+
+        if t >= 1.0 {
+            commands.entity(entity).remove::<Tween>();
+        }
+    }
+}
+
+/// link the animation players of the scenes to the aliens
+pub fn attach_animators(
+    added: Query<Entity, Added<AnimationPlayer>>,
+    parents: Query<&Parent>,
+    mut aliens: Query<&mut Alien>,
+) {
+    for entity in &added {
+        for ancestor in parents.iter_ancestors(entity) {
+            if let Ok(mut alien) = aliens.get_mut(ancestor) {
+                alien.animator = Some(entity);
+                break;
+            }
+        }
+    }
+}
+
+/// play the walk clip when moving, or the idle clip
+pub fn play_clips(
+    model: Res<AlienModel>,
+    gltfs: Res<Assets<Gltf>>,
+    mut aliens: Query<(&mut Alien, Has<Tween>)>,
+    mut players: Query<&mut AnimationPlayer>,
+) {
+    let Some(gltf) = gltfs.get(&model.gltf) else {
+        return;
+    };
+    let clip = |name: &str| {
+        gltf.named_animations
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, handle)| handle.clone())
+    };
+
+    for (mut alien, moving) in &mut aliens {
+        if alien.walking == Some(moving) {
+            continue;
+        }
+        let Some(animator) = alien.animator else {
+            continue;
+        };
+        let Some(handle) = clip(if moving { "walk" } else { "idle" }) else {
+            continue;
+        };
+        if let Ok(mut player) = players.get_mut(animator) {
+            player
+                .play_with_transition(handle, CLIP_TRANSITION)
+                .repeat();
+            alien.walking = Some(moving);
+        }
+    }
+}
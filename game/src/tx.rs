@@ -0,0 +1,344 @@
+//! Tracking of the lobby transactions: wait for the hash from wallet, poll the
+//! receipt, decode the revert reason and show the result as toasts.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_web3::{RecvError, H160};
+use serde_json::{json, Value};
+use std::fmt;
+use z4_bevy::RoomId;
+
+use crate::{rpc::ChainRpc, wallet::Wallet, Game};
+
+/// seconds to wait the hash from wallet, e.g. rejected by user
+const SEND_TIMEOUT: f64 = 60.0;
+/// seconds to show a toast
+const TOAST_SECONDS: f64 = 6.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Approve,
+    CreateRoom,
+    JoinRoom(RoomId),
+    StartRoom(RoomId),
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Approve => write!(f, "Approve ticket"),
+            Action::CreateRoom => write!(f, "Create room"),
+            Action::JoinRoom(room) => write!(f, "Join room {}", room),
+            Action::StartRoom(room) => write!(f, "Start room {}", room),
+        }
+    }
+}
+
+struct Tx {
+    action: Action,
+    from: String,
+    to: H160,
+    data: Vec<u8>,
+    // none until the wallet sent it
+    hash: Option<String>,
+    sent_at: f64,
+    // no hash from wallet in time, still waits its late response
+    expired: bool,
+}
+
+struct Toast {
+    text: String,
+    error: bool,
+    until: f64,
+}
+
+#[derive(Resource, Default)]
+pub struct Transactions {
+    rpc: ChainRpc,
+    txs: Vec<Tx>,
+    toasts: Vec<Toast>,
+    now: f64,
+}
+
+impl Transactions {
+    /// send the action by wallet and track it
+    pub fn send(&mut self, wallet: &Wallet, from: &str, action: Action, to: H160, data: Vec<u8>) {
+        if self.is_pending(&action) {
+            return;
+        }
+        wallet.send(from, to, data.clone());
+        self.toast(format!("{}: confirm in wallet", action), false);
+        self.txs.push(Tx {
+            action,
+            from: from.to_owned(),
+            to,
+            data,
+            hash: None,
+            sent_at: self.now,
+            expired: false,
+        });
+    }
+
+    /// the action is sent and not finished, disable the duplicate clicks
+    pub fn is_pending(&self, action: &Action) -> bool {
+        self.txs
+            .iter()
+            .any(|tx| &tx.action == action && !tx.expired)
+    }
+
+    fn toast(&mut self, text: String, error: bool) {
+        if error {
+            warn!("{}", text);
+        } else {
+            info!("{}", text);
+        }
+        self.toasts.push(Toast {
+            text,
+            error,
+            until: self.now + TOAST_SECONDS,
+        });
+    }
+
+    fn finish(&mut self, hash: &str, result: Result<(), String>) {
+        if let Some(i) = self
+            .txs
+            .iter()
+            .position(|tx| tx.hash.as_deref() == Some(hash))
+        {
+            let tx = self.txs.remove(i);
+            match result {
+                Ok(()) => self.toast(format!("{}: success", tx.action), false),
+                Err(reason) => self.toast(format!("{}: {}", tx.action, reason), true),
+            }
+        }
+    }
+
+    /// the receipt: pending when null, and replay the reverted call for the reason
+    fn receipt(&mut self, url: &str, hash: &str, receipt: Value) {
+        if receipt.is_null() {
+            return;
+        }
+        if receipt["status"].as_str() != Some("0x0") {
+            return self.finish(hash, Ok(()));
+        }
+
+        let Some(tx) = self.txs.iter().find(|tx| tx.hash.as_deref() == Some(hash)) else {
+            return;
+        };
+        let call = json!({
+            "from": tx.from,
+            "to": format!("{:?}", tx.to),
+            "data": format!("0x{}", hex::encode(&tx.data)),
+        });
+        // the state before the block, the reverted call passes after it
+        let block = receipt["blockNumber"]
+            .as_str()
+            .and_then(|n| u64::from_str_radix(n.trim_start_matches("0x"), 16).ok())
+            .map(|n| format!("0x{:x}", n.saturating_sub(1)))
+            .unwrap_or("latest".to_owned());
+        self.rpc.request(
+            url,
+            &format!("revert:{}", hash),
+            "eth_call",
+            json!([call, block]),
+        );
+    }
+}
+
+/// the revert message of the RoomMarket and SimpleGame error codes
+fn error_message(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "RM01" => "sequencer staking is not enough",
+        "RM02" => "room is not in this status",
+        "RM03" => "room is full or already joined",
+        "RM04" => "sequencer staking is not enough to accept the room",
+        "RM05" => "not the sequencer of the room",
+        "RM06" => "only the room creator can start it",
+        "RM07" => "invalid proof",
+        "RM08" => "invalid proof signer",
+        "RM09" => "invalid player limit",
+        "SG01" => "room is not over",
+        "SG02" => "result is not of the room",
+        "SG03" => "result players are not of the room",
+        _ => return None,
+    })
+}
+
+/// decode the reason from the RPC error, e.g. the `Error(string)` revert data
+/// or `execution reverted: RM03`
+pub fn revert_reason(error: &str) -> String {
+    let mut text = error.to_owned();
+    let data = serde_json::from_str::<Value>(error)
+        .ok()
+        .and_then(|v| v.get("data").cloned())
+        .and_then(|d| d.get("data").cloned().or(Some(d)))
+        .and_then(|d| d.as_str().map(|s| s.to_owned()));
+    if let Some(data) = data {
+        // Error(string) selector, offset, length and the string
+        let bytes = hex::decode(data.trim_start_matches("0x")).unwrap_or_default();
+        if bytes.len() >= 68 && bytes[..4] == [0x08, 0xc3, 0x79, 0xa0] {
+            let len = bytes[36..68]
+                .iter()
+                .fold(0usize, |l, b| (l << 8) | *b as usize);
+            if let Some(s) = bytes.get(68..68 + len) {
+                text = String::from_utf8_lossy(s).into_owned();
+            }
+        }
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    for code in chars.windows(4) {
+        let code: String = code.iter().collect();
+        if let Some(message) = error_message(&code) {
+            return format!("{} ({})", message, code);
+        }
+    }
+    let message = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|v| v["message"].as_str().map(|s| s.to_owned()));
+    message.unwrap_or(text)
+}
+
+/// the hashes from wallet, and the RPC results
+pub fn receive(
+    time: Res<Time>,
+    wallet: Wallet,
+    game: Res<Game>,
+    mut transactions: ResMut<Transactions>,
+) {
+    transactions.now = time.elapsed_seconds_f64();
+
+    loop {
+        let sent = match wallet.recv_transaction() {
+            Ok(sent) => sent,
+            Err(RecvError::Empty) | Err(RecvError::Closed) => break,
+        };
+        // wallets send the transactions in order, the expired ones are the oldest
+        let Some(i) = transactions.txs.iter().position(|tx| tx.hash.is_none()) else {
+            continue;
+        };
+        match sent {
+            Ok(hash) => {
+                info!("{} tx: {}", transactions.txs[i].action, hash);
+                transactions.txs[i].hash = Some(hash);
+                transactions.txs[i].expired = false;
+            }
+            Err(err) => {
+                let tx = transactions.txs.remove(i);
+                if !tx.expired {
+                    transactions.toast(format!("{}: {}", tx.action, revert_reason(&err)), true);
+                }
+            }
+        }
+    }
+
+    while let Some((id, result)) = transactions.rpc.recv() {
+        match (id.strip_prefix("revert:"), result) {
+            (Some(hash), Ok(_)) => transactions.finish(hash, Err("reverted".to_owned())),
+            (Some(hash), Err(err)) => transactions.finish(hash, Err(revert_reason(&err))),
+            (None, Ok(receipt)) => transactions.receipt(&game.network.rpc, &id, receipt),
+            (None, Err(err)) => warn!("Receipt {}: {}", id, err),
+        }
+    }
+
+    // keep the expired ones, so their late responses are not taken by the newer
+    let now = transactions.now;
+    let mut expired = vec![];
+    for tx in transactions.txs.iter_mut() {
+        if tx.hash.is_none() && !tx.expired && now - tx.sent_at > SEND_TIMEOUT {
+            tx.expired = true;
+            expired.push(tx.action.clone());
+        }
+    }
+    for action in expired {
+        transactions.toast(format!("{}: not sent by wallet", action), true);
+    }
+}
+
+/// poll the receipts of the sent transactions
+pub fn poll(game: Res<Game>, transactions: Res<Transactions>) {
+    for hash in transactions.txs.iter().filter_map(|tx| tx.hash.as_ref()) {
+        transactions.rpc.request(
+            &game.network.rpc,
+            hash,
+            "eth_getTransactionReceipt",
+            json!([hash]),
+        );
+    }
+}
+
+pub fn toasts(mut contexts: EguiContexts, mut transactions: ResMut<Transactions>) {
+    let now = transactions.now;
+    transactions.toasts.retain(|toast| toast.until > now);
+    if transactions.toasts.is_empty() {
+        return;
+    }
+
+    egui::Area::new(egui::Id::new("toasts"))
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .show(contexts.ctx_mut(), |ui| {
+            for toast in &transactions.toasts {
+                let color = if toast.error {
+                    egui::Color32::from_rgb(220, 80, 80)
+                } else {
+                    egui::Color32::from_rgb(120, 200, 120)
+                };
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.colored_label(color, &toast.text);
+                });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the abi encoded `Error(string)` revert data
+    fn error_data(reason: &str) -> String {
+        let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+        let mut word = |n: usize| {
+            let mut w = [0u8; 32];
+            w[24..].copy_from_slice(&(n as u64).to_be_bytes());
+            data.extend_from_slice(&w);
+        };
+        word(32);
+        word(reason.len());
+        data.extend_from_slice(reason.as_bytes());
+        data.resize(68 + reason.len().div_ceil(32) * 32, 0);
+        format!("0x{}", hex::encode(data))
+    }
+
+    #[test]
+    fn revert_reason_error_string() {
+        let error = json!({
+            "code": 3,
+            "message": "execution reverted",
+            "data": error_data("RM03"),
+        });
+        assert_eq!(
+            revert_reason(&error.to_string()),
+            "room is full or already joined (RM03)"
+        );
+
+        let error = json!({ "code": -32000, "data": { "data": error_data("out of cakes") } });
+        assert_eq!(revert_reason(&error.to_string()), "out of cakes");
+    }
+
+    #[test]
+    fn revert_reason_execution_reverted() {
+        assert_eq!(
+            revert_reason("execution reverted: RM03"),
+            "room is full or already joined (RM03)"
+        );
+
+        let error = json!({ "code": -32000, "message": "execution reverted: SG02" });
+        assert_eq!(
+            revert_reason(&error.to_string()),
+            "result is not of the room (SG02)"
+        );
+
+        let error = json!({ "code": -32000, "message": "insufficient funds" });
+        assert_eq!(revert_reason(&error.to_string()), "insufficient funds");
+    }
+}
@@ -1,13 +1,35 @@
 use bevy::prelude::*;
-use bevy_web3::{EthWallet, RecvError, Token};
+use bevy_web3::Token;
 use z4_bevy::{FetchRoomStatusTimer, RoomMarket};
 
 use crate::{
-    style::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
+    style::{set_text, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
+    tx::{Action, Transactions},
+    wallet::Wallet,
     Game, GameState,
 };
 
-pub fn setup(mut commands: Commands) {
+/// the waiting texts, updated in place
+#[derive(Component)]
+pub enum WaitText {
+    Room,
+    Sequencer,
+    Server,
+    Countdown,
+}
+
+/// the players list, respawned only when the players change
+#[derive(Component, Default)]
+pub struct WaitPlayers(Vec<String>);
+
+/// the start button of the room creator
+#[derive(Component)]
+pub struct StartButton;
+
+#[derive(Component)]
+pub struct StartLabel;
+
+pub fn setup(mut commands: Commands, mut game: ResMut<Game>) {
     commands.spawn(Camera2dBundle::default());
 
     commands.spawn(FetchRoomStatusTimer::seconds(2.0));
@@ -15,23 +37,18 @@ pub fn setup(mut commands: Commands) {
         1.0,
         TimerMode::Repeating,
     )));
-}
-
-pub fn cleanup(mut game: ResMut<Game>) {
-    game.waiting_entity = None;
-}
 
-pub fn show(mut commands: Commands, market: Res<RoomMarket>, mut game: ResMut<Game>) {
-    if let Some(entity) = game.waiting_entity {
-        if commands.get_entity(entity).is_some() {
-            commands.entity(entity).despawn_recursive();
-        }
-    }
-
-    let is_admin = if let Some(waiting) = &market.waiting {
-        waiting.players[0] == game.account
-    } else {
-        false
+    let text = |text: WaitText| {
+        (
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 30.,
+                    ..default()
+                },
+            ),
+            text,
+        )
     };
 
     game.waiting_entity = Some(
@@ -48,108 +65,148 @@ pub fn show(mut commands: Commands, market: Res<RoomMarket>, mut game: ResMut<Ga
                 ..default()
             })
             .with_children(|parent| {
-                if let Some(waiting) = &market.waiting {
-                    parent.spawn(TextBundle::from_section(
-                        format!("Waiting room: {}", waiting.room),
-                        TextStyle {
-                            font_size: 30.,
+                parent.spawn(text(WaitText::Room));
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
                             ..default()
                         },
-                    ));
+                        ..default()
+                    },
+                    WaitPlayers::default(),
+                ));
+                parent.spawn(text(WaitText::Sequencer));
+                parent.spawn(text(WaitText::Server));
 
-                    for player in &waiting.players {
-                        parent.spawn(TextBundle::from_section(
-                            format!("Player: {}", player),
-                            TextStyle {
-                                font_size: 30.,
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(400.0),
+                                height: Val::Px(65.0),
+                                border: UiRect::all(Val::Px(5.0)),
+                                margin: UiRect::all(Val::Px(20.0)),
+                                // horizontally center child text
+                                justify_content: JustifyContent::Center,
+                                // vertically center child text
+                                align_items: AlignItems::Center,
+                                display: Display::None,
                                 ..default()
                             },
-                        ));
-                    }
-
-                    // show: sequencer
-                    parent.spawn(TextBundle::from_section(
-                        format!(
-                            "Sequencer: {}",
-                            waiting.sequencer.as_ref().unwrap_or(&"...".to_owned())
-                        ),
-                        TextStyle {
-                            font_size: 30.,
+                            border_color: BorderColor(Color::BLACK),
+                            background_color: NORMAL_BUTTON.into(),
                             ..default()
                         },
-                    ));
-
-                    // show: sequencer
-                    parent.spawn(TextBundle::from_section(
-                        format!(
-                            "Server: {}",
-                            waiting.websocket.as_ref().unwrap_or(&"...".to_owned())
-                        ),
-                        TextStyle {
-                            font_size: 30.,
-                            ..default()
-                        },
-                    ));
-
-                    if waiting.websocket.is_some() && game.countdown == 0 {
-                        game.countdown = 10;
-                        game.room = waiting.room;
-                        game.server = waiting.websocket.clone().unwrap();
-                    }
-                }
-
-                if game.countdown == 0 {
-                    if is_admin {
-                        parent
-                            .spawn(ButtonBundle {
-                                style: Style {
-                                    width: Val::Px(400.0),
-                                    height: Val::Px(65.0),
-                                    border: UiRect::all(Val::Px(5.0)),
-                                    margin: UiRect::all(Val::Px(20.0)),
-                                    // horizontally center child text
-                                    justify_content: JustifyContent::Center,
-                                    // vertically center child text
-                                    align_items: AlignItems::Center,
+                        StartButton,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            TextBundle::from_section(
+                                "Start Now!",
+                                TextStyle {
+                                    font_size: 30.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
                                     ..default()
                                 },
-                                border_color: BorderColor(Color::BLACK),
-                                background_color: NORMAL_BUTTON.into(),
-                                ..default()
-                            })
-                            .with_children(|parent| {
-                                parent.spawn(TextBundle::from_section(
-                                    "Start Now!",
-                                    TextStyle {
-                                        font_size: 30.0,
-                                        color: Color::rgb(0.9, 0.9, 0.9),
-                                        ..default()
-                                    },
-                                ));
-                            });
-                    }
-                } else {
-                    // show: sequencer
-                    parent.spawn(TextBundle::from_section(
-                        format!("Countdown: {}", game.countdown),
-                        TextStyle {
-                            font_size: 30.,
-                            ..default()
-                        },
-                    ));
-                }
+                            ),
+                            StartLabel,
+                        ));
+                    });
+
+                parent.spawn(text(WaitText::Countdown));
             })
             .id(),
     );
 }
 
-pub fn _start(wallet: Res<EthWallet>) {
-    match wallet.recv_transaction() {
-        Ok(tx) => {
-            info!("tx: {:?}", tx);
+pub fn cleanup(mut game: ResMut<Game>) {
+    game.waiting_entity = None;
+}
+
+/// start the countdown when the sequencer accepted the room
+pub fn ready(market: Res<RoomMarket>, mut game: ResMut<Game>) {
+    if let Some(waiting) = &market.waiting {
+        if waiting.websocket.is_some() && game.countdown == 0 {
+            game.countdown = 10;
+            game.room = waiting.room;
+            game.server = waiting.websocket.clone().unwrap();
         }
-        Err(RecvError::Empty) => {}
-        Err(RecvError::Closed) => {}
+    }
+}
+
+/// update the waiting room in place when the market or game changes
+pub fn show(
+    mut commands: Commands,
+    market: Res<RoomMarket>,
+    game: Res<Game>,
+    mut texts: Query<(&WaitText, Mut<Text>)>,
+    mut players: Query<(Entity, &mut WaitPlayers)>,
+    mut button: Query<&mut Style, With<StartButton>>,
+) {
+    let waiting = market.waiting.as_ref();
+    for (kind, text) in &mut texts {
+        let value = match (kind, waiting) {
+            (WaitText::Room, Some(waiting)) => format!("Waiting room: {}", waiting.room),
+            (WaitText::Sequencer, Some(waiting)) => format!(
+                "Sequencer: {}",
+                waiting.sequencer.as_deref().unwrap_or("...")
+            ),
+            (WaitText::Server, Some(waiting)) => {
+                format!("Server: {}", waiting.websocket.as_deref().unwrap_or("..."))
+            }
+            (WaitText::Countdown, _) if game.countdown > 0 => {
+                format!("Countdown: {}", game.countdown)
+            }
+            _ => String::new(),
+        };
+        set_text(text, &value);
+    }
+
+    let accounts = waiting.map(|w| w.players.clone()).unwrap_or_default();
+    for (entity, mut list) in &mut players {
+        if list.0 == accounts {
+            continue;
+        }
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity).with_children(|parent| {
+            for player in &accounts {
+                parent.spawn(TextBundle::from_section(
+                    format!("Player: {}", player),
+                    TextStyle {
+                        font_size: 30.,
+                        ..default()
+                    },
+                ));
+            }
+        });
+        list.0 = accounts.clone();
+    }
+
+    // only the creator starts the room, before the countdown
+    let is_admin = waiting.and_then(|w| w.players.first()) == Some(&game.account);
+    let display = if is_admin && game.countdown == 0 {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    for mut style in &mut button {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+}
+
+/// the start label follows the pending start transaction
+pub fn start_label(
+    game: Res<Game>,
+    transactions: Res<Transactions>,
+    mut labels: Query<Mut<Text>, With<StartLabel>>,
+) {
+    let pending = transactions.is_pending(&Action::StartRoom(game.room));
+    for text in &mut labels {
+        set_text(text, if pending { "Starting..." } else { "Start Now!" });
     }
 }
 
@@ -175,7 +232,8 @@ pub fn countdown(
 }
 
 pub fn start_button(
-    wallet: Res<EthWallet>,
+    wallet: Wallet,
+    mut transactions: ResMut<Transactions>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, &mut BorderColor),
         (Changed<Interaction>, With<Button>),
@@ -191,7 +249,8 @@ pub fn start_button(
                 let data = game
                     .contract
                     .encode("startRoom", &[Token::Uint(game.room.into())]);
-                wallet.send(&game.account, game.contract.address, data);
+                let (action, to) = (Action::StartRoom(game.room), game.contract.address);
+                transactions.send(&wallet, &game.account, action, to, data);
             }
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON.into();
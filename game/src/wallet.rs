@@ -0,0 +1,335 @@
+//! The wallet of the client: the browser extension by `bevy_web3`, or a local
+//! private key which signs and sends the transactions to the RPC by itself.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_web3::{EthWallet, RecvError, H160};
+use ethers_core::{k256::ecdsa::SigningKey, types::Address};
+use std::sync::{
+    mpsc::{channel, Receiver, Sender, TryRecvError},
+    Mutex,
+};
+
+use crate::config::Network;
+
+enum Request {
+    Connect,
+    // switch to the chain RPC
+    Switch(String),
+    // to, data
+    Send(Address, Vec<u8>),
+}
+
+/// Wallet with a local private key, selected when `WALLET_KEY` is set.
+/// The requests are handled in a worker thread, one by one.
+#[derive(Resource)]
+pub struct LocalWallet {
+    requests: Mutex<Sender<Request>>,
+    // account, chain id
+    accounts: Mutex<Receiver<(String, u64)>>,
+    // transaction hash, or the send error
+    transactions: Mutex<Receiver<Result<String, String>>>,
+}
+
+impl LocalWallet {
+    /// `WALLET_KEY`: hex private key, `WALLET_RPC`: JSON-RPC endpoint, default the network RPC
+    pub fn from_env(network_rpc: &str) -> Option<Self> {
+        let key = std::env::var("WALLET_KEY").ok()?;
+        let rpc = std::env::var("WALLET_RPC").unwrap_or(network_rpc.to_owned());
+        match hex::decode(key.trim().trim_start_matches("0x"))
+            .ok()
+            .and_then(|bytes| SigningKey::from_slice(&bytes).ok())
+        {
+            Some(key) => Self::new(key, rpc),
+            None => {
+                error!("Invalid WALLET_KEY");
+                None
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(_key: SigningKey, _rpc: String) -> Option<Self> {
+        None
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(key: SigningKey, rpc: String) -> Option<Self> {
+        let (requests, requests_recv) = channel();
+        let (accounts_send, accounts) = channel();
+        let (transactions_send, transactions) = channel();
+        std::thread::spawn(move || {
+            local::worker(key, rpc, requests_recv, accounts_send, transactions_send)
+        });
+
+        Some(Self {
+            requests: Mutex::new(requests),
+            accounts: Mutex::new(accounts),
+            transactions: Mutex::new(transactions),
+        })
+    }
+}
+
+fn try_recv<T>(receiver: &Mutex<Receiver<T>>) -> Result<T, RecvError> {
+    match receiver.lock().map(|r| r.try_recv()) {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(TryRecvError::Empty)) => Err(RecvError::Empty),
+        _ => Err(RecvError::Closed),
+    }
+}
+
+/// The wallet selected at startup.
+#[derive(SystemParam)]
+pub struct Wallet<'w> {
+    browser: Option<ResMut<'w, EthWallet>>,
+    local: Option<Res<'w, LocalWallet>>,
+}
+
+impl<'w> Wallet<'w> {
+    pub fn connect(&self) {
+        if let Some(local) = &self.local {
+            if let Ok(requests) = local.requests.lock() {
+                let _ = requests.send(Request::Connect);
+            }
+        } else if let Some(browser) = &self.browser {
+            browser.connect();
+        }
+    }
+
+    /// the connected account and chain id
+    pub fn recv_account(&mut self) -> Result<(String, u64), RecvError> {
+        if let Some(local) = &self.local {
+            try_recv(&local.accounts)
+        } else if let Some(browser) = &mut self.browser {
+            browser.recv_account()
+        } else {
+            Err(RecvError::Closed)
+        }
+    }
+
+    pub fn send(&self, from: &str, to: H160, data: Vec<u8>) {
+        if let Some(local) = &self.local {
+            if let Ok(requests) = local.requests.lock() {
+                let _ = requests.send(Request::Send(Address::from(to.0), data));
+            }
+        } else if let Some(browser) = &self.browser {
+            browser.send(from, to, data);
+        }
+    }
+
+    /// ask the wallet to switch to the chain, add the chain if not in wallet
+    pub fn switch_chain(&self, network: &Network) {
+        if let Some(local) = &self.local {
+            if let Ok(requests) = local.requests.lock() {
+                let _ = requests.send(Request::Switch(network.rpc.clone()));
+            }
+        } else if self.browser.is_some() {
+            #[cfg(target_arch = "wasm32")]
+            browser::switch_chain(network.clone());
+            #[cfg(not(target_arch = "wasm32"))]
+            warn!("No browser wallet to switch chain {}", network.chain_id);
+        }
+    }
+
+    /// the sent transaction hash, or the error when the local wallet failed to send
+    pub fn recv_transaction(&self) -> Result<Result<String, String>, RecvError> {
+        if let Some(local) = &self.local {
+            try_recv(&local.transactions)
+        } else if let Some(browser) = &self.browser {
+            browser.recv_transaction().map(|tx| Ok(format!("{:?}", tx)))
+        } else {
+            Err(RecvError::Closed)
+        }
+    }
+}
+
+/// EIP-3326 & EIP-3085 requests to the injected `window.ethereum`.
+#[cfg(target_arch = "wasm32")]
+mod browser {
+    use bevy::log::error;
+    use js_sys::{Array, Function, Object, Promise, Reflect};
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+    use crate::config::Network;
+
+    fn object(fields: &[(&str, JsValue)]) -> JsValue {
+        let object = Object::new();
+        for (key, value) in fields {
+            let _ = Reflect::set(&object, &JsValue::from_str(key), value);
+        }
+        object.into()
+    }
+
+    async fn request(method: &str, params: JsValue) -> Result<JsValue, JsValue> {
+        let window = web_sys::window().ok_or(JsValue::from_str("no window"))?;
+        let ethereum = Reflect::get(&window, &JsValue::from_str("ethereum"))?;
+        let request: Function =
+            Reflect::get(&ethereum, &JsValue::from_str("request"))?.dyn_into()?;
+        let args = object(&[("method", method.into()), ("params", params)]);
+        let promise: Promise = request.call1(&ethereum, &args)?.dyn_into()?;
+        JsFuture::from(promise).await
+    }
+
+    pub fn switch_chain(network: Network) {
+        spawn_local(async move {
+            let chain_id = format!("0x{:x}", network.chain_id);
+            let params = Array::of1(&object(&[("chainId", chain_id.clone().into())]));
+            let err = match request("wallet_switchEthereumChain", params.into()).await {
+                Ok(_) => return,
+                Err(err) => err,
+            };
+
+            // 4902: the chain is not added in wallet
+            let code = Reflect::get(&err, &JsValue::from_str("code"))
+                .ok()
+                .and_then(|c| c.as_f64());
+            if code != Some(4902.0) {
+                error!("Switch chain: {:?}", err);
+                return;
+            }
+            let currency = object(&[
+                ("name", network.currency.clone().into()),
+                ("symbol", network.currency.into()),
+                ("decimals", 18.into()),
+            ]);
+            let params = Array::of1(&object(&[
+                ("chainId", chain_id.into()),
+                ("chainName", network.chain_name.into()),
+                ("rpcUrls", Array::of1(&network.rpc.into()).into()),
+                ("nativeCurrency", currency),
+            ]));
+            if let Err(err) = request("wallet_addEthereumChain", params.into()).await {
+                error!("Add chain: {:?}", err);
+            }
+        });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod local {
+    use ethers_core::{
+        k256::ecdsa::SigningKey,
+        types::{
+            transaction::eip2718::TypedTransaction, Address, Signature, TransactionRequest, U256,
+        },
+        utils::secret_key_to_address,
+    };
+    use serde_json::{json, Value};
+    use std::sync::mpsc::{Receiver, Sender};
+
+    use super::Request;
+
+    fn rpc(url: &str, method: &str, params: Value) -> Result<Value, String> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = ureq::post(url)
+            .send_json(request)
+            .map_err(|e| e.to_string())?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+
+        if let Some(error) = response.get("error") {
+            return Err(error["message"].as_str().unwrap_or("rpc error").to_owned());
+        }
+        Ok(response["result"].clone())
+    }
+
+    fn quantity(url: &str, method: &str, params: Value) -> Result<U256, String> {
+        serde_json::from_value(rpc(url, method, params)?).map_err(|e| e.to_string())
+    }
+
+    /// sign the legacy transaction with EIP-155, and send it
+    fn send(
+        key: &SigningKey,
+        url: &str,
+        chain: u64,
+        nonce: U256,
+        to: Address,
+        data: Vec<u8>,
+    ) -> Result<String, String> {
+        let from = secret_key_to_address(key);
+        let call = json!({ "from": from, "to": to, "data": format!("0x{}", hex::encode(&data)) });
+        let gas = quantity(url, "eth_estimateGas", json!([call]))?;
+        let gas_price = quantity(url, "eth_gasPrice", json!([]))?;
+
+        let tx: TypedTransaction = TransactionRequest::new()
+            .from(from)
+            .to(to)
+            .data(data)
+            .nonce(nonce)
+            .gas(gas * 12 / 10)
+            .gas_price(gas_price)
+            .chain_id(chain)
+            .into();
+        let (sig, recid) = key
+            .sign_prehash_recoverable(tx.sighash().as_bytes())
+            .map_err(|e| e.to_string())?;
+        let signature = Signature {
+            r: U256::from_big_endian(&sig.r().to_bytes()),
+            s: U256::from_big_endian(&sig.s().to_bytes()),
+            v: recid.to_byte() as u64 + 35 + chain * 2,
+        };
+
+        let raw = tx.rlp_signed(&signature);
+        let hash = rpc(url, "eth_sendRawTransaction", json!([raw]))?;
+        Ok(hash.as_str().unwrap_or_default().to_owned())
+    }
+
+    pub(super) fn worker(
+        key: SigningKey,
+        url: String,
+        requests: Receiver<Request>,
+        accounts: Sender<(String, u64)>,
+        transactions: Sender<Result<String, String>>,
+    ) {
+        let account = secret_key_to_address(&key);
+        let mut url = url;
+        let mut chain = 0;
+        // next nonce, fetched again after failure
+        let mut nonce: Option<U256> = None;
+
+        while let Ok(request) = requests.recv() {
+            if let Request::Switch(rpc) = &request {
+                url = rpc.clone();
+                nonce = None;
+            }
+
+            match request {
+                Request::Connect | Request::Switch(_) => {
+                    match quantity(&url, "eth_chainId", json!([])) {
+                        Ok(id) => {
+                            chain = id.as_u64();
+                            let _ = accounts.send((format!("{:?}", account), chain));
+                        }
+                        Err(err) => bevy::log::error!("Local wallet {}: {}", url, err),
+                    }
+                }
+                Request::Send(to, data) => {
+                    if chain == 0 {
+                        chain = quantity(&url, "eth_chainId", json!([]))
+                            .map(|id| id.as_u64())
+                            .unwrap_or_default();
+                    }
+                    let next = match nonce {
+                        Some(next) => Ok(next),
+                        None => {
+                            quantity(&url, "eth_getTransactionCount", json!([account, "pending"]))
+                        }
+                    };
+                    match next.and_then(|next| {
+                        send(&key, &url, chain, next, to, data).map(|hash| (next, hash))
+                    }) {
+                        Ok((next, hash)) => {
+                            nonce = Some(next + 1);
+                            let _ = transactions.send(Ok(hash));
+                        }
+                        Err(err) => {
+                            nonce = None;
+                            bevy::log::error!("Local wallet send: {}", err);
+                            let _ = transactions.send(Err(err));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
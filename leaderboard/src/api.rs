@@ -0,0 +1,86 @@
+//! The JSON API of the leaderboard:
+//! - `GET /leaderboard?offset=0&limit=20`: the synced block and the ranked players
+//! - `GET /players/:address?limit=20`: the rank, totals and latest records of player
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use ethers_core::types::Address;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+use tower_http::cors::CorsLayer;
+
+use crate::Leaderboard;
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct Page {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Page {
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(20).min(MAX_LIMIT)
+    }
+}
+
+type Board = Arc<RwLock<Leaderboard>>;
+
+pub fn router(board: Board) -> Router {
+    Router::new()
+        .route("/leaderboard", get(leaderboard))
+        .route("/players/:address", get(player))
+        .layer(CorsLayer::permissive())
+        .with_state(board)
+}
+
+/// serve the API at the address
+pub async fn serve(board: Board, addr: SocketAddr) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(board)).await
+}
+
+async fn leaderboard(State(board): State<Board>, Query(page): Query<Page>) -> Json<Value> {
+    let board = board.read().unwrap();
+    let players: Vec<Value> = board
+        .top(page.offset, page.limit())
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let mut value = json!(entry);
+            value["rank"] = json!(page.offset + i + 1);
+            value
+        })
+        .collect();
+
+    Json(json!({
+        "block": board.next_block.saturating_sub(1),
+        "total": board.players(),
+        "players": players,
+    }))
+}
+
+async fn player(
+    State(board): State<Board>,
+    Path(address): Path<String>,
+    Query(page): Query<Page>,
+) -> Result<Json<Value>, StatusCode> {
+    let address: Address = address.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let board = board.read().unwrap();
+    let (rank, entry) = board.player(&address).ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut value = json!(entry);
+    value["rank"] = json!(rank);
+    value["history"] = json!(board.history(&address, page.limit()));
+    Ok(Json(value))
+}
@@ -0,0 +1,100 @@
+//! Ingest the `Ranking` events from the chain RPC to the leaderboard, the new
+//! blocks are fetched in batches on every sync.
+
+use ethers_core::types::Address;
+use serde_json::{json, Value};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::{ranking_topic, Leaderboard};
+
+/// max blocks of one `eth_getLogs`
+const BATCH_BLOCKS: u64 = 5000;
+
+/// the `eth_getLogs` filter of the ranking events in blocks
+pub fn filter(game: Address, from: u64, to: u64) -> Value {
+    json!({
+        "address": game,
+        "fromBlock": format!("0x{:x}", from),
+        "toBlock": format!("0x{:x}", to),
+        "topics": [ranking_topic()],
+    })
+}
+
+pub struct Indexer {
+    client: reqwest::Client,
+    url: String,
+    game: Address,
+    board: Arc<RwLock<Leaderboard>>,
+}
+
+impl Indexer {
+    pub fn new(url: &str, game: Address, board: Arc<RwLock<Leaderboard>>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_owned(),
+            game,
+            board,
+        }
+    }
+
+    async fn rpc(&self, method: &str, params: Value) -> Result<Value, String> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(error) = response.get("error") {
+            return Err(error["message"].as_str().unwrap_or("rpc error").to_owned());
+        }
+        Ok(response["result"].clone())
+    }
+
+    /// ingest the blocks to the latest, return the number of new records
+    pub async fn sync(&self) -> Result<usize, String> {
+        let latest = self.rpc("eth_blockNumber", json!([])).await?;
+        let latest = latest
+            .as_str()
+            .and_then(|b| u64::from_str_radix(b.trim_start_matches("0x"), 16).ok())
+            .ok_or("invalid block number")?;
+
+        let mut count = 0;
+        loop {
+            let from = self.board.read().map_err(|e| e.to_string())?.next_block;
+            if from > latest {
+                break;
+            }
+            let to = latest.min(from + BATCH_BLOCKS - 1);
+            let logs = self
+                .rpc("eth_getLogs", json!([filter(self.game, from, to)]))
+                .await?;
+            count += self
+                .board
+                .write()
+                .map_err(|e| e.to_string())?
+                .ingest(&logs, to);
+        }
+        Ok(count)
+    }
+
+    /// sync on every interval
+    pub async fn run(self, interval: Duration) {
+        loop {
+            match self.sync().await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("ingested {} ranking records", count),
+                Err(err) => tracing::warn!("sync {}: {}", self.url, err),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
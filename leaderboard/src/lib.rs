@@ -0,0 +1,278 @@
+//! The global leaderboard of `SimpleGame`: win and reward totals of every
+//! player with the history, indexed from the `Ranking` events.
+
+pub mod api;
+pub mod indexer;
+
+use ethers_core::{
+    abi::{decode, ParamType},
+    types::{Address, H256, U256},
+    utils::keccak256,
+};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// `Ranking(address player, uint256 win, uint256 reward)`, no indexed params
+pub fn ranking_topic() -> H256 {
+    H256(keccak256("Ranking(address,uint256,uint256)"))
+}
+
+/// the amounts in decimal string
+fn decimal<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+/// A `Ranking` event.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Record {
+    pub block: u64,
+    pub transaction: H256,
+    pub player: Address,
+    #[serde(serialize_with = "decimal")]
+    pub win: U256,
+    #[serde(serialize_with = "decimal")]
+    pub reward: U256,
+}
+
+/// The totals of a player.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    pub player: Address,
+    #[serde(serialize_with = "decimal")]
+    pub win: U256,
+    #[serde(serialize_with = "decimal")]
+    pub reward: U256,
+    // ranked rooms
+    pub games: u64,
+}
+
+#[derive(Default)]
+pub struct Leaderboard {
+    entries: HashMap<Address, Entry>,
+    history: Vec<Record>,
+    // the next block to ingest
+    pub next_block: u64,
+}
+
+impl Leaderboard {
+    pub fn new(start_block: u64) -> Self {
+        Self {
+            next_block: start_block,
+            ..Default::default()
+        }
+    }
+
+    /// ingest the `eth_getLogs` result to the block `to`, other events are
+    /// skipped. Return the number of ingested records.
+    pub fn ingest(&mut self, logs: &Value, to: u64) -> usize {
+        let topic = ranking_topic();
+        let types = [
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+        ];
+
+        let mut count = 0;
+        for log in logs.as_array().into_iter().flatten() {
+            let topics: Vec<H256> =
+                serde_json::from_value(log["topics"].clone()).unwrap_or_default();
+            if topics.first() != Some(&topic) || log["removed"] == Value::Bool(true) {
+                continue;
+            }
+            let data = hex::decode(log["data"].as_str().unwrap_or("").trim_start_matches("0x"))
+                .unwrap_or_default();
+            let Ok(tokens) = decode(&types, &data) else {
+                continue;
+            };
+            let block = log["blockNumber"]
+                .as_str()
+                .and_then(|b| u64::from_str_radix(b.trim_start_matches("0x"), 16).ok())
+                .unwrap_or(to);
+
+            self.record(Record {
+                block,
+                transaction: serde_json::from_value(log["transactionHash"].clone())
+                    .unwrap_or_default(),
+                player: tokens[0].clone().into_address().unwrap_or_default(),
+                win: tokens[1].clone().into_uint().unwrap_or_default(),
+                reward: tokens[2].clone().into_uint().unwrap_or_default(),
+            });
+            count += 1;
+        }
+
+        self.next_block = self.next_block.max(to + 1);
+        count
+    }
+
+    fn record(&mut self, record: Record) {
+        let entry = self.entries.entry(record.player).or_insert_with(|| Entry {
+            player: record.player,
+            ..Default::default()
+        });
+        entry.win += record.win;
+        entry.reward += record.reward;
+        entry.games += 1;
+        self.history.push(record);
+    }
+
+    /// players by win, then reward
+    pub fn top(&self, offset: usize, limit: usize) -> Vec<Entry> {
+        self.ranked().into_iter().skip(offset).take(limit).collect()
+    }
+
+    fn ranked(&self) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| {
+            b.win
+                .cmp(&a.win)
+                .then(b.reward.cmp(&a.reward))
+                .then(a.player.cmp(&b.player))
+        });
+        entries
+    }
+
+    /// the rank from 1 and the totals of player
+    pub fn player(&self, player: &Address) -> Option<(usize, Entry)> {
+        self.ranked()
+            .into_iter()
+            .enumerate()
+            .find(|(_, e)| e.player == *player)
+            .map(|(i, e)| (i + 1, e))
+    }
+
+    /// the latest records of player
+    pub fn history(&self, player: &Address, limit: usize) -> Vec<Record> {
+        self.history
+            .iter()
+            .rev()
+            .filter(|r| r.player == *player)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub fn players(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alien_cake_addict_market::{rpc::Chain, Market};
+    use ethers_core::{
+        abi::{encode, Token},
+        types::Bytes,
+    };
+    use serde_json::json;
+
+    fn send(chain: &mut Chain, from: Address, name: &str, params: &[Token]) {
+        let function = chain
+            .market
+            .abi
+            .functions_by_name(name)
+            .unwrap()
+            .iter()
+            .find(|f| f.inputs.len() == params.len())
+            .unwrap();
+        let data = Bytes::from(function.encode_input(params).unwrap());
+        let call = json!({ "from": from, "to": chain.market.address, "data": data });
+        let res =
+            chain.handle(json!({ "id": 1, "method": "eth_sendTransaction", "params": [call] }));
+        let receipt = chain.handle(
+            json!({ "id": 2, "method": "eth_getTransactionReceipt", "params": [res["result"]] }),
+        );
+        assert_eq!(receipt["result"]["status"], json!("0x1"), "{}", name);
+    }
+
+    /// a room of players with the rank is over in the market
+    fn play_room(chain: &mut Chain, sequencer: Address, room: u64, rank: [Address; 2]) {
+        let pk = Token::FixedBytes(vec![0u8; 32]);
+        let params = [
+            Token::Uint(10.into()),
+            Token::Bool(false),
+            Token::Address(rank[0]),
+            pk.clone(),
+        ];
+        send(chain, rank[0], "createRoom", &params);
+        let params = [Token::Uint(room.into()), Token::Address(rank[1]), pk];
+        send(chain, rank[1], "joinRoom", &params);
+        send(
+            chain,
+            sequencer,
+            "acceptRoom",
+            &[Token::Uint(room.into()), Token::Bytes(vec![])],
+        );
+
+        let result = encode(&[
+            Token::Uint(room.into()),
+            Token::Array(rank.iter().map(|p| Token::Address(*p)).collect()),
+            Token::Array(rank.iter().map(|p| Token::Address(*p)).collect()),
+            Token::Array(vec![Token::Uint(3.into()), Token::Uint(1.into())]),
+            Token::Array(rank.iter().map(|p| Token::Address(*p)).collect()),
+            Token::FixedBytes(vec![0u8; 32]),
+            Token::FixedBytes(vec![0u8; 32]),
+        ]);
+        let params = [
+            Token::Uint(room.into()),
+            Token::Bytes(result),
+            Token::Bytes(vec![]),
+        ];
+        send(chain, sequencer, "overRoomWithZk", &params);
+    }
+
+    #[test]
+    fn leaderboard_works() {
+        let game = Address::repeat_byte(9);
+        let sequencer = Address::repeat_byte(8);
+        let (a, b, c) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        let mut chain = Chain::new(31337, Market::new(game, 2, 100000).unwrap());
+        let params = [
+            Token::String("http://127.0.0.1:8080".to_owned()),
+            Token::String("ws://127.0.0.1:8000".to_owned()),
+            Token::Uint(100.into()),
+        ];
+        send(&mut chain, sequencer, "stakeSequencer", &params);
+        play_room(&mut chain, sequencer, 100000, [a, b]);
+
+        // ingest in two batches, the other events are skipped
+        let mut board = Leaderboard::new(0);
+        let filter = indexer::filter(game, 0, chain.block);
+        let logs = chain.handle(json!({ "id": 1, "method": "eth_getLogs", "params": [filter] }));
+        assert_eq!(board.ingest(&logs["result"], chain.block), 2);
+        assert_eq!(board.next_block, chain.block + 1);
+
+        play_room(&mut chain, sequencer, 100001, [c, a]);
+        let filter = indexer::filter(game, board.next_block, chain.block);
+        let logs = chain.handle(json!({ "id": 1, "method": "eth_getLogs", "params": [filter] }));
+        assert_eq!(board.ingest(&logs["result"], chain.block), 2);
+
+        let top = board.top(0, 10);
+        assert_eq!(
+            top.iter()
+                .map(|e| (e.player, e.win.as_u64()))
+                .collect::<Vec<_>>(),
+            vec![(a, 4), (c, 3), (b, 1)]
+        );
+        assert_eq!(board.top(1, 1)[0].player, c);
+        assert_eq!(
+            board.player(&a).map(|(rank, e)| (rank, e.games)),
+            Some((1, 2))
+        );
+        assert_eq!(board.player(&Address::repeat_byte(4)), None);
+
+        let history = board.history(&a, 10);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].win, 1.into());
+        assert!(history[0].block > history[1].block);
+        assert_eq!(
+            serde_json::to_value(&top[0]).unwrap()["reward"],
+            json!("20")
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use alien_cake_addict_leaderboard::{api::serve, indexer::Indexer, Leaderboard};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let rpc: String = env_or("RPC", "http://127.0.0.1:8545".to_owned());
+    let game = env_or(
+        "GAMES",
+        "0xe7f1725E7734CE288F8367e1Bb143E90bb3F0512"
+            .parse()
+            .unwrap(),
+    );
+    let start_block = env_or("START_BLOCK", 0);
+    let port: u16 = env_or("PORT", 8090);
+    let interval = Duration::from_secs(env_or("INTERVAL", 5));
+
+    let board = Arc::new(RwLock::new(Leaderboard::new(start_block)));
+    tokio::spawn(Indexer::new(&rpc, game, board.clone()).run(interval));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    tracing::info!("leaderboard of {:?} from {} at {}", game, rpc, addr);
+    serve(board, addr).await.expect("Down");
+}
@@ -0,0 +1,873 @@
+//! In-memory stand-in of the RoomMarket contract (as `SimpleGame.sol`), served
+//! behind the ethereum JSON-RPC, so the node, pozk and the game client can be
+//! tested on a laptop without a chain.
+
+use aca_core::{is_bot, result_signer, result_types};
+use ethers_core::{
+    abi::{decode, encode, parse_abi, Abi, Token},
+    types::{Address, U256},
+};
+use std::collections::{BTreeMap, HashMap};
+
+pub mod rpc;
+
+/// The RoomMarket & SimpleGame functions and events served by the market.
+pub const ABI: &[&str] = &[
+    "function stakeSequencer(string http, string websocket, uint256 amount)",
+    "function unstakeSequencer(uint256 amount)",
+    "function createRoom(uint256 ticket, bool viewable, address peer, bytes32 pk) returns (uint256)",
+    // the z4 contracts have an extra salt
+    "function createRoom(uint256 ticket, bool viewable, address peer, bytes32 pk, bytes32 salt) returns (uint256)",
+    "function createRoomWithParams(uint256 ticket, bool viewable, address peer, bytes32 pk, uint256 limit, bytes params) returns (uint256)",
+    "function joinRoom(uint256 roomId, address peer, bytes32 pk) returns (uint256)",
+    "function startRoom(uint256 roomId)",
+    "function acceptRoom(uint256 roomId, bytes params)",
+    "function overRoomWithZk(uint256 roomId, bytes data, bytes proof)",
+    "function overRoomWithThreshold(uint256 roomId, bytes data, bytes proof)",
+    "function claimRoom(uint256 roomId)",
+    "function restartRoom(uint256 roomId)",
+    "function roomInfo(uint256 roomId) view returns (address[], address, address, uint256, uint8)",
+    // the public getter of room, without the arrays
+    "function rooms(uint256 roomId) view returns (bool, uint256, uint256, address, uint256, uint256, bytes, uint8)",
+    "function isSequencer(address sequencer) view returns (bool)",
+    "function sequencers(address sequencer) view returns (string, string, uint256)",
+    "function nextRoomId() view returns (uint256)",
+    "function playerLimit() view returns (uint256)",
+    "function token() view returns (address)",
+    "event StakeSequencer(address sequencer, string http, string websocket, uint256 staking)",
+    "event UnstakeSequencer(address sequencer, uint256 staking)",
+    "event CreateRoom(uint256 room, address game, uint256 reward, bool viewable, address player, address peer, bytes32 pk)",
+    "event RoomParams(uint256 room, uint256 limit, bytes params)",
+    "event JoinRoom(uint256 room, address player, address peer, bytes32 pk)",
+    "event StartRoom(uint256 room, address game)",
+    "event AcceptRoom(uint256 room, address sequencer, string websocket, uint256 locked, bytes params)",
+    "event OverRoom(uint256 room)",
+    "event ClaimRoom(uint256 room)",
+    "event Ranking(address player, uint256 win, uint256 reward)",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoomStatus {
+    #[default]
+    None,
+    Opening,
+    Waiting,
+    Playing,
+    Over,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Room {
+    pub players: Vec<Address>,
+    pub peers: Vec<Address>,
+    pub pks: Vec<[u8; 32]>,
+    pub viewable: bool,
+    pub ticket: U256,
+    pub reward: U256,
+    pub sequencer: Address,
+    pub locked: U256,
+    pub site: usize,
+    pub result: Vec<u8>,
+    pub status: RoomStatus,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Sequencer {
+    pub http: String,
+    pub websocket: String,
+    pub staking: U256,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rank {
+    pub win: U256,
+    pub reward: U256,
+}
+
+/// An emitted event: the ABI event name and its params.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub name: &'static str,
+    pub params: Vec<Token>,
+}
+
+/// The contract state. Tokens are not transferred, there is no ERC20 in memory.
+#[derive(Clone)]
+pub struct Market {
+    pub abi: Abi,
+    // the game contract address
+    pub address: Address,
+    pub token: Address,
+    pub min_staking: U256,
+    pub player_room_lock: U256,
+    pub player_limit: usize,
+    pub next_room_id: u64,
+    pub rooms: BTreeMap<u64, Room>,
+    pub sequencers: HashMap<Address, Sequencer>,
+    pub ranking: HashMap<Address, Rank>,
+    // events of the running call
+    events: Vec<Event>,
+}
+
+fn addresses(token: &Token) -> Vec<Address> {
+    token
+        .clone()
+        .into_array()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|t| t.into_address())
+        .collect()
+}
+
+impl Market {
+    /// the player limit must not be zero, same as the constructor
+    pub fn new(address: Address, player_limit: usize, start_room_id: u64) -> Result<Self, String> {
+        if player_limit == 0 {
+            return Err("RM09".to_owned());
+        }
+        Ok(Self {
+            abi: parse_abi(ABI).expect("invalid market abi"),
+            address,
+            token: Address::zero(),
+            min_staking: U256::zero(),
+            player_room_lock: U256::zero(),
+            player_limit,
+            next_room_id: start_room_id,
+            rooms: BTreeMap::new(),
+            sequencers: HashMap::new(),
+            ranking: HashMap::new(),
+            events: vec![],
+        })
+    }
+
+    /// Call the contract with the calldata, return the abi encoded output and
+    /// the emitted events, or the revert reason. Reverted calls change nothing.
+    pub fn call(&mut self, sender: Address, data: &[u8]) -> Result<(Vec<u8>, Vec<Event>), String> {
+        let selector = data.get(..4).ok_or("no function selector")?;
+        let function = self
+            .abi
+            .functions()
+            .find(|f| f.short_signature() == selector)
+            .ok_or("unknown function")?
+            .clone();
+        let params = Params(
+            function
+                .decode_input(&data[4..])
+                .map_err(|_| "invalid params")?,
+        );
+
+        let mut next = self.clone();
+        next.events.clear();
+        let output = next.execute(sender, &function.name, &params)?;
+
+        let events = std::mem::take(&mut next.events);
+        *self = next;
+        Ok((encode(&output), events))
+    }
+
+    fn execute(
+        &mut self,
+        sender: Address,
+        name: &str,
+        params: &Params,
+    ) -> Result<Vec<Token>, String> {
+        let output = match name {
+            "stakeSequencer" => {
+                self.stake_sequencer(sender, params.string(0), params.string(1), params.uint(2));
+                vec![]
+            }
+            "unstakeSequencer" => {
+                self.unstake_sequencer(sender, params.uint(0))?;
+                vec![]
+            }
+            "createRoom" => {
+                let room = self.create_room(
+                    sender,
+                    params.uint(0),
+                    params.bool(1),
+                    params.address(2),
+                    params.bytes32(3),
+                );
+                vec![Token::Uint(room.into())]
+            }
+            "createRoomWithParams" => {
+                let room = self.create_room_with_params(
+                    sender,
+                    params.uint(0),
+                    params.bool(1),
+                    params.address(2),
+                    params.bytes32(3),
+                    params.uint(4),
+                    params.bytes(5),
+                )?;
+                vec![Token::Uint(room.into())]
+            }
+            "joinRoom" => {
+                let site =
+                    self.join_room(sender, params.room(0), params.address(1), params.bytes32(2))?;
+                vec![Token::Uint(site.into())]
+            }
+            "startRoom" => {
+                self.start_room(params.room(0))?;
+                vec![]
+            }
+            "acceptRoom" => {
+                self.accept_room(sender, params.room(0), params.bytes(1))?;
+                vec![]
+            }
+            "overRoomWithZk" => {
+                self.over_room_with_zk(sender, params.room(0), params.bytes(1))?;
+                vec![]
+            }
+            "overRoomWithThreshold" => {
+                self.over_room_with_threshold(
+                    sender,
+                    params.room(0),
+                    params.bytes(1),
+                    &params.bytes(2),
+                )?;
+                vec![]
+            }
+            "claimRoom" => {
+                self.claim_room(params.room(0))?;
+                vec![]
+            }
+            "restartRoom" => {
+                self.restart_room(sender, params.room(0))?;
+                vec![]
+            }
+            "roomInfo" => {
+                let room = self.rooms.get(&params.room(0)).cloned().unwrap_or_default();
+                vec![
+                    Token::Array(room.players.into_iter().map(Token::Address).collect()),
+                    Token::Address(self.address),
+                    Token::Address(room.sequencer),
+                    Token::Uint(room.site.into()),
+                    Token::Uint((room.status as u8).into()),
+                ]
+            }
+            "rooms" => {
+                let room = self.rooms.get(&params.room(0)).cloned().unwrap_or_default();
+                vec![
+                    Token::Bool(room.viewable),
+                    Token::Uint(room.ticket),
+                    Token::Uint(room.reward),
+                    Token::Address(room.sequencer),
+                    Token::Uint(room.locked),
+                    Token::Uint(room.site.into()),
+                    Token::Bytes(room.result),
+                    Token::Uint((room.status as u8).into()),
+                ]
+            }
+            "isSequencer" => {
+                let staking = self
+                    .sequencers
+                    .get(&params.address(0))
+                    .map(|s| s.staking)
+                    .unwrap_or_default();
+                vec![Token::Bool(staking >= self.min_staking)]
+            }
+            "sequencers" => {
+                let s = self
+                    .sequencers
+                    .get(&params.address(0))
+                    .cloned()
+                    .unwrap_or_default();
+                vec![
+                    Token::String(s.http),
+                    Token::String(s.websocket),
+                    Token::Uint(s.staking),
+                ]
+            }
+            "nextRoomId" => vec![Token::Uint(self.next_room_id.into())],
+            "playerLimit" => vec![Token::Uint(self.player_limit.into())],
+            "token" => vec![Token::Address(self.token)],
+            _ => return Err("unknown function".to_owned()),
+        };
+        Ok(output)
+    }
+
+    fn emit(&mut self, name: &'static str, params: Vec<Token>) {
+        self.events.push(Event { name, params });
+    }
+
+    pub fn stake_sequencer(
+        &mut self,
+        sender: Address,
+        http: String,
+        websocket: String,
+        amount: U256,
+    ) {
+        let sequencer = self.sequencers.entry(sender).or_default();
+        sequencer.staking += amount;
+        sequencer.http = http;
+        sequencer.websocket = websocket;
+
+        let params = vec![
+            Token::Address(sender),
+            Token::String(sequencer.http.clone()),
+            Token::String(sequencer.websocket.clone()),
+            Token::Uint(sequencer.staking),
+        ];
+        self.emit("StakeSequencer", params);
+    }
+
+    pub fn unstake_sequencer(&mut self, sender: Address, amount: U256) -> Result<(), String> {
+        let sequencer = self.sequencers.entry(sender).or_default();
+        if sequencer.staking < amount {
+            return Err("RM01".to_owned());
+        }
+        sequencer.staking -= amount;
+
+        let params = vec![Token::Address(sender), Token::Uint(sequencer.staking)];
+        self.emit("UnstakeSequencer", params);
+        Ok(())
+    }
+
+    pub fn create_room(
+        &mut self,
+        sender: Address,
+        ticket: U256,
+        viewable: bool,
+        peer: Address,
+        pk: [u8; 32],
+    ) -> u64 {
+        let id = self.next_room_id;
+        let room = Room {
+            players: vec![sender],
+            peers: vec![peer],
+            pks: vec![pk],
+            viewable,
+            ticket,
+            reward: ticket,
+            site: self.player_limit - 1,
+            status: RoomStatus::Opening,
+            ..Default::default()
+        };
+        self.rooms.insert(id, room);
+        self.next_room_id += 1;
+
+        let params = vec![
+            Token::Uint(id.into()),
+            Token::Address(self.address),
+            Token::Uint(ticket),
+            Token::Bool(viewable),
+            Token::Address(sender),
+            Token::Address(peer),
+            Token::FixedBytes(pk.to_vec()),
+        ];
+        self.emit("CreateRoom", params);
+        id
+    }
+
+    /// create room with less players than the limit, and the game params
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_room_with_params(
+        &mut self,
+        sender: Address,
+        ticket: U256,
+        viewable: bool,
+        peer: Address,
+        pk: [u8; 32],
+        limit: U256,
+        params: Vec<u8>,
+    ) -> Result<u64, String> {
+        if limit.is_zero() || limit > self.player_limit.into() {
+            return Err("RM09".to_owned());
+        }
+
+        let id = self.create_room(sender, ticket, viewable, peer, pk);
+        if let Some(room) = self.rooms.get_mut(&id) {
+            room.site = limit.as_usize() - 1;
+        }
+        self.emit(
+            "RoomParams",
+            vec![
+                Token::Uint(id.into()),
+                Token::Uint(limit),
+                Token::Bytes(params),
+            ],
+        );
+        Ok(id)
+    }
+
+    pub fn join_room(
+        &mut self,
+        sender: Address,
+        id: u64,
+        peer: Address,
+        pk: [u8; 32],
+    ) -> Result<usize, String> {
+        let room = self.room(id, RoomStatus::Opening)?;
+        if room.site == 0 || room.players.contains(&sender) {
+            return Err("RM03".to_owned());
+        }
+
+        room.players.push(sender);
+        room.peers.push(peer);
+        room.pks.push(pk);
+        room.reward += room.ticket;
+        room.site -= 1;
+
+        let site = room.site;
+        if site == 0 {
+            room.status = RoomStatus::Waiting;
+        }
+
+        let params = vec![
+            Token::Uint(id.into()),
+            Token::Address(sender),
+            Token::Address(peer),
+            Token::FixedBytes(pk.to_vec()),
+        ];
+        self.emit("JoinRoom", params);
+        if site == 0 {
+            self.emit_start(id);
+        }
+        Ok(site)
+    }
+
+    pub fn start_room(&mut self, id: u64) -> Result<(), String> {
+        self.room(id, RoomStatus::Opening)?.status = RoomStatus::Waiting;
+        self.emit_start(id);
+        Ok(())
+    }
+
+    fn emit_start(&mut self, id: u64) {
+        let params = vec![Token::Uint(id.into()), Token::Address(self.address)];
+        self.emit("StartRoom", params);
+    }
+
+    pub fn accept_room(&mut self, sender: Address, id: u64, params: Vec<u8>) -> Result<(), String> {
+        let players = self.rooms.get(&id).map(|r| r.players.len()).unwrap_or(0);
+        let lock = self.player_room_lock * U256::from(players);
+        let sequencer = self.sequencers.get(&sender).cloned().unwrap_or_default();
+        if sequencer.staking < self.min_staking || sequencer.staking < lock {
+            return Err("RM04".to_owned());
+        }
+
+        let room = self.room(id, RoomStatus::Waiting)?;
+        room.sequencer = sender;
+        room.status = RoomStatus::Playing;
+        room.locked = lock;
+        self.sequencers.entry(sender).or_default().staking -= lock;
+
+        let params = vec![
+            Token::Uint(id.into()),
+            Token::Address(sender),
+            Token::String(sequencer.websocket),
+            Token::Uint(lock),
+            Token::Bytes(params),
+        ];
+        self.emit("AcceptRoom", params);
+        Ok(())
+    }
+
+    // TODO verify zkp, same as the contract
+    pub fn over_room_with_zk(
+        &mut self,
+        sender: Address,
+        id: u64,
+        data: Vec<u8>,
+    ) -> Result<(), String> {
+        let room = self.room(id, RoomStatus::Playing)?;
+        if room.sequencer != sender {
+            return Err("RM05".to_owned());
+        }
+
+        room.result = data;
+        self.over_room(id)
+    }
+
+    pub fn over_room_with_threshold(
+        &mut self,
+        sender: Address,
+        id: u64,
+        data: Vec<u8>,
+        proof: &[u8],
+    ) -> Result<(), String> {
+        let room = self.room(id, RoomStatus::Playing)?;
+        if room.sequencer != sender {
+            return Err("RM05".to_owned());
+        }
+
+        // proof is operations head || signature (r, s, v)
+        if proof.len() != 97 {
+            return Err("RM07".to_owned());
+        }
+        let mut head = [0u8; 32];
+        head.copy_from_slice(&proof[..32]);
+        if result_signer(id, &data, head, &proof[32..]) != Some(room.sequencer) {
+            return Err("RM08".to_owned());
+        }
+
+        room.result = data;
+        self.over_room(id)
+    }
+
+    fn over_room(&mut self, id: u64) -> Result<(), String> {
+        let room = self.room(id, RoomStatus::Playing)?;
+        room.status = RoomStatus::Over;
+        let (sequencer, amount) = (room.sequencer, room.locked + room.reward);
+        self.sequencers.entry(sequencer).or_default().staking += amount;
+
+        self.claim_room(id)?;
+        self.emit("OverRoom", vec![Token::Uint(id.into())]);
+        Ok(())
+    }
+
+    /// SimpleGame claim: the first of the rank wins the reward except a
+    /// ticket, the second wins the ticket.
+    pub fn claim_room(&mut self, id: u64) -> Result<(), String> {
+        let room = self.room(id, RoomStatus::Over).map_err(|_| "SG01")?.clone();
+
+        let result = decode(&result_types(), &room.result).map_err(|_| "invalid room result")?;
+        if result[0].clone().into_uint() != Some(id.into()) {
+            return Err("SG02".to_owned());
+        }
+        // the moves are signed by the peers of the room players, bots are not in the room
+        let (players, peers) = (addresses(&result[1]), addresses(&result[2]));
+        let joined = |player: &Address, peer: &Address| {
+            let i = room.players.iter().position(|p| p == player);
+            i.and_then(|i| room.peers.get(i)) == Some(peer)
+        };
+        if players.len() != peers.len()
+            || players
+                .iter()
+                .zip(&peers)
+                .any(|(player, peer)| !is_bot(player) && !joined(player, peer))
+        {
+            return Err("SG03".to_owned());
+        }
+        let winners = addresses(&result[4]);
+
+        if !winners.is_empty() {
+            self.rank(winners[0], 3, room.reward - room.ticket);
+        }
+        if winners.len() > 1 {
+            self.rank(winners[1], 1, room.ticket);
+        }
+
+        self.rooms.remove(&id);
+        self.emit("ClaimRoom", vec![Token::Uint(id.into())]);
+        Ok(())
+    }
+
+    fn rank(&mut self, player: Address, win: u64, reward: U256) {
+        let rank = self.ranking.entry(player).or_default();
+        rank.win += win.into();
+        rank.reward += reward;
+
+        let params = vec![
+            Token::Address(player),
+            Token::Uint(win.into()),
+            Token::Uint(reward),
+        ];
+        self.emit("Ranking", params);
+    }
+
+    pub fn restart_room(&mut self, sender: Address, id: u64) -> Result<(), String> {
+        let room = self.room(id, RoomStatus::Playing)?;
+        if room.players[0] != sender {
+            return Err("RM06".to_owned());
+        }
+
+        room.status = RoomStatus::Waiting;
+        self.emit_start(id);
+        Ok(())
+    }
+
+    /// the room must be in the status
+    fn room(&mut self, id: u64, status: RoomStatus) -> Result<&mut Room, String> {
+        match self.rooms.get_mut(&id) {
+            Some(room) if room.status == status => Ok(room),
+            _ => Err("RM02".to_owned()),
+        }
+    }
+}
+
+/// Decoded call params, the types are checked by the ABI.
+struct Params(Vec<Token>);
+
+impl Params {
+    fn uint(&self, i: usize) -> U256 {
+        self.0[i].clone().into_uint().unwrap_or_default()
+    }
+
+    fn room(&self, i: usize) -> u64 {
+        let id = self.uint(i);
+        if id > U256::from(u64::MAX) {
+            u64::MAX
+        } else {
+            id.as_u64()
+        }
+    }
+
+    fn bool(&self, i: usize) -> bool {
+        self.0[i].clone().into_bool().unwrap_or_default()
+    }
+
+    fn address(&self, i: usize) -> Address {
+        self.0[i].clone().into_address().unwrap_or_default()
+    }
+
+    fn bytes(&self, i: usize) -> Vec<u8> {
+        self.0[i].clone().into_bytes().unwrap_or_default()
+    }
+
+    fn bytes32(&self, i: usize) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        if let Some(b) = self.0[i].clone().into_fixed_bytes() {
+            bytes.copy_from_slice(&b);
+        }
+        bytes
+    }
+
+    fn string(&self, i: usize) -> String {
+        self.0[i].clone().into_string().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers_core::abi::ParamType;
+
+    fn calldata(market: &Market, name: &str, params: &[Token]) -> Vec<u8> {
+        let function = market
+            .abi
+            .functions_by_name(name)
+            .unwrap()
+            .iter()
+            .find(|f| f.inputs.len() == params.len())
+            .unwrap();
+        function.encode_input(params).unwrap()
+    }
+
+    #[test]
+    fn room_lifecycle_works() {
+        let game = Address::repeat_byte(9);
+        let sequencer = Address::repeat_byte(8);
+        let players = [Address::repeat_byte(1), Address::repeat_byte(2)];
+        assert_eq!(Market::new(game, 0, 100000).err().unwrap(), "RM09");
+        let mut market = Market::new(game, 2, 100000).unwrap();
+
+        let data = calldata(
+            &market,
+            "stakeSequencer",
+            &[
+                Token::String("http://127.0.0.1:8080".to_owned()),
+                Token::String("ws://127.0.0.1:8000".to_owned()),
+                Token::Uint(100.into()),
+            ],
+        );
+        market.call(sequencer, &data).unwrap();
+
+        let data = calldata(
+            &market,
+            "createRoom",
+            &[
+                Token::Uint(10.into()),
+                Token::Bool(true),
+                Token::Address(players[0]),
+                Token::FixedBytes(vec![0u8; 32]),
+            ],
+        );
+        let (output, events) = market.call(players[0], &data).unwrap();
+        assert_eq!(output, encode(&[Token::Uint(100000.into())]));
+        assert_eq!(events[0].name, "CreateRoom");
+
+        // the room is full and waiting after join
+        let data = calldata(
+            &market,
+            "joinRoom",
+            &[
+                Token::Uint(100000.into()),
+                Token::Address(players[1]),
+                Token::FixedBytes(vec![0u8; 32]),
+            ],
+        );
+        let (_, events) = market.call(players[1], &data).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.name).collect::<Vec<_>>(),
+            vec!["JoinRoom", "StartRoom"]
+        );
+        assert_eq!(market.call(players[1], &data).unwrap_err(), "RM02");
+
+        let data = calldata(
+            &market,
+            "acceptRoom",
+            &[Token::Uint(100000.into()), Token::Bytes(vec![])],
+        );
+        market.call(sequencer, &data).unwrap();
+        assert_eq!(market.rooms[&100000].status, RoomStatus::Playing);
+
+        // the room is viewable by the public getter
+        let data = calldata(&market, "rooms", &[Token::Uint(100000.into())]);
+        let (output, _) = market.call(players[0], &data).unwrap();
+        let room = market
+            .abi
+            .function("rooms")
+            .unwrap()
+            .decode_output(&output)
+            .unwrap();
+        assert_eq!(room[0], Token::Bool(true));
+        assert_eq!(room[7], Token::Uint(3.into()));
+
+        // only the sequencer can over the room, nothing changed when reverted
+        let result = encode(&[
+            Token::Uint(100000.into()),
+            Token::Array(players.iter().map(|p| Token::Address(*p)).collect()),
+            Token::Array(players.iter().map(|p| Token::Address(*p)).collect()),
+            Token::Array(vec![Token::Uint(1.into()), Token::Uint(3.into())]),
+            Token::Array(vec![Token::Address(players[1]), Token::Address(players[0])]),
+            Token::FixedBytes(vec![0u8; 32]),
+            Token::FixedBytes(vec![0u8; 32]),
+        ]);
+        let data = calldata(
+            &market,
+            "overRoomWithZk",
+            &[
+                Token::Uint(100000.into()),
+                Token::Bytes(result.clone()),
+                Token::Bytes(vec![]),
+            ],
+        );
+        assert_eq!(market.call(players[0], &data).unwrap_err(), "RM05");
+        assert_eq!(market.rooms[&100000].status, RoomStatus::Playing);
+
+        // the result must be of the room
+        let mut tokens = decode(&result_types(), &result).unwrap();
+        tokens[0] = Token::Uint(1.into());
+        let other = calldata(
+            &market,
+            "overRoomWithZk",
+            &[
+                Token::Uint(100000.into()),
+                Token::Bytes(encode(&tokens)),
+                Token::Bytes(vec![]),
+            ],
+        );
+        assert_eq!(market.call(sequencer, &other).unwrap_err(), "SG02");
+        assert_eq!(market.rooms[&100000].status, RoomStatus::Playing);
+
+        // the moves must be signed by the peers of the room
+        let mut tokens = decode(&result_types(), &result).unwrap();
+        tokens[2] = Token::Array(vec![Token::Address(sequencer), Token::Address(sequencer)]);
+        let forged = calldata(
+            &market,
+            "overRoomWithZk",
+            &[
+                Token::Uint(100000.into()),
+                Token::Bytes(encode(&tokens)),
+                Token::Bytes(vec![]),
+            ],
+        );
+        assert_eq!(market.call(sequencer, &forged).unwrap_err(), "SG03");
+        assert_eq!(market.rooms[&100000].status, RoomStatus::Playing);
+
+        let (_, events) = market.call(sequencer, &data).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.name).collect::<Vec<_>>(),
+            vec!["Ranking", "Ranking", "ClaimRoom", "OverRoom"]
+        );
+        assert!(market.rooms.is_empty());
+        assert_eq!(
+            market.ranking[&players[1]],
+            Rank {
+                win: 3.into(),
+                reward: 10.into()
+            }
+        );
+        assert_eq!(
+            market.ranking[&players[0]],
+            Rank {
+                win: 1.into(),
+                reward: 10.into()
+            }
+        );
+        assert_eq!(market.sequencers[&sequencer].staking, U256::from(120));
+
+        // less players than the limit
+        let options = |limit: u64| {
+            calldata(
+                &market,
+                "createRoomWithParams",
+                &[
+                    Token::Uint(0.into()),
+                    Token::Bool(false),
+                    Token::Address(players[0]),
+                    Token::FixedBytes(vec![0u8; 32]),
+                    Token::Uint(limit.into()),
+                    Token::Bytes(vec![1]),
+                ],
+            )
+        };
+        let (invalid, valid) = (options(3), options(1));
+        assert_eq!(market.call(players[0], &invalid).unwrap_err(), "RM09");
+        let (output, events) = market.call(players[0], &valid).unwrap();
+        let room = decode(&[ParamType::Uint(256)], &output).unwrap()[0]
+            .clone()
+            .into_uint()
+            .unwrap()
+            .as_u64();
+        assert_eq!(events[1].name, "RoomParams");
+        assert_eq!(market.rooms[&room].site, 0);
+    }
+
+    #[test]
+    fn claim_single_winner_works() {
+        let game = Address::repeat_byte(9);
+        let sequencer = Address::repeat_byte(8);
+        let player = Address::repeat_byte(1);
+        // the reserved bot account
+        let bot = Address::from_low_u64_be(1);
+        let mut market = Market::new(game, 2, 100000).unwrap();
+        market.sequencers.entry(sequencer).or_default().staking = 100.into();
+        market.rooms.insert(
+            100000,
+            Room {
+                players: vec![player],
+                peers: vec![player],
+                ticket: 10.into(),
+                reward: 20.into(),
+                sequencer,
+                status: RoomStatus::Playing,
+                ..Default::default()
+            },
+        );
+
+        // one player with bots, only the player is ranked
+        let result = encode(&[
+            Token::Uint(100000.into()),
+            Token::Array(vec![Token::Address(player), Token::Address(bot)]),
+            Token::Array(vec![Token::Address(player), Token::Address(bot)]),
+            Token::Array(vec![Token::Uint(1.into()), Token::Uint(3.into())]),
+            Token::Array(vec![Token::Address(player)]),
+            Token::FixedBytes(vec![0u8; 32]),
+            Token::FixedBytes(vec![0u8; 32]),
+        ]);
+        let data = calldata(
+            &market,
+            "overRoomWithZk",
+            &[
+                Token::Uint(100000.into()),
+                Token::Bytes(result),
+                Token::Bytes(vec![]),
+            ],
+        );
+        let (_, events) = market.call(sequencer, &data).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.name).collect::<Vec<_>>(),
+            vec!["Ranking", "ClaimRoom", "OverRoom"]
+        );
+        assert_eq!(
+            market.ranking[&player],
+            Rank {
+                win: 3.into(),
+                reward: 10.into()
+            }
+        );
+        assert!(market.rooms.is_empty());
+    }
+}
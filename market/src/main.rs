@@ -0,0 +1,37 @@
+use alien_cake_addict_market::{
+    rpc::{serve, Chain},
+    Market,
+};
+use std::net::SocketAddr;
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let chain_id = env_or("CHAIN_ID", 31337);
+    let port: u16 = env_or("PORT", 8545);
+    let game = env_or(
+        "GAMES",
+        "0xe7f1725E7734CE288F8367e1Bb143E90bb3F0512"
+            .parse()
+            .unwrap(),
+    );
+    let player_limit = env_or("PLAYER_LIMIT", 4);
+    let start_room_id = env_or("START_ROOM_ID", 100000);
+
+    let market = Market::new(game, player_limit, start_room_id).expect("invalid PLAYER_LIMIT");
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    tracing::info!("market {:?} of chain {} at {}", game, chain_id, addr);
+
+    serve(Chain::new(chain_id, market), addr)
+        .await
+        .expect("Down");
+}
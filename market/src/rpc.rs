@@ -0,0 +1,439 @@
+//! The ethereum JSON-RPC of the market chain. Every transaction is mined in
+//! its own block at once, only the market contract exists.
+
+use axum::{extract::State, routing::post, Json, Router};
+use ethers_core::{
+    abi::{encode, Token},
+    types::{
+        transaction::eip2718::TypedTransaction, Address, Block, Bytes, Log, TransactionReceipt,
+        H256, U256, U64,
+    },
+    utils::{keccak256, rlp::Rlp},
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tower_http::cors::CorsLayer;
+
+use crate::{Event, Market};
+
+/// JSON-RPC error: code, message and data
+type RpcError = (i64, String, Option<Value>);
+
+const GAS: u64 = 1_000_000;
+const GAS_PRICE: u64 = 1_000_000_000;
+
+pub struct Chain {
+    pub chain_id: u64,
+    pub market: Market,
+    // latest block number
+    pub block: u64,
+    logs: Vec<Log>,
+    transactions: HashMap<H256, Value>,
+    receipts: HashMap<H256, TransactionReceipt>,
+    nonces: HashMap<Address, u64>,
+}
+
+impl Chain {
+    pub fn new(chain_id: u64, market: Market) -> Self {
+        Self {
+            chain_id,
+            market,
+            block: 0,
+            logs: vec![],
+            transactions: HashMap::new(),
+            receipts: HashMap::new(),
+            nonces: HashMap::new(),
+        }
+    }
+
+    /// handle the single or batch JSON-RPC request
+    pub fn handle(&mut self, request: Value) -> Value {
+        match request {
+            Value::Array(requests) => {
+                Value::Array(requests.into_iter().map(|r| self.handle_one(r)).collect())
+            }
+            request => self.handle_one(request),
+        }
+    }
+
+    fn handle_one(&mut self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = request
+            .get("params")
+            .and_then(|p| p.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        match self.request(method, &params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message, data)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message, "data": data }
+            }),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        let result = match method {
+            "eth_chainId" => json!(U64::from(self.chain_id)),
+            "net_version" => json!(self.chain_id.to_string()),
+            "web3_clientVersion" => json!("alien-cake-addict-market"),
+            "eth_syncing" => json!(false),
+            "eth_accounts" => json!([]),
+            "eth_blockNumber" => json!(U64::from(self.block)),
+            "eth_gasPrice" => json!(U256::from(GAS_PRICE)),
+            "eth_maxPriorityFeePerGas" => json!(U256::zero()),
+            "eth_feeHistory" => json!({
+                "oldestBlock": U64::from(self.block),
+                "baseFeePerGas": [U256::from(GAS_PRICE), U256::from(GAS_PRICE)],
+                "gasUsedRatio": [0.0],
+                "reward": [[U256::zero()]],
+            }),
+            "eth_getBalance" => json!(U256::exp10(24)),
+            "eth_getCode" => {
+                let address = address(params.first())?;
+                if address == self.market.address {
+                    json!("0x00")
+                } else {
+                    json!("0x")
+                }
+            }
+            "eth_getTransactionCount" => {
+                let address = address(params.first())?;
+                json!(U256::from(self.nonces.get(&address).copied().unwrap_or(0)))
+            }
+            "eth_getBlockByNumber" => {
+                let number = block_number(params.first(), self.block);
+                if number > self.block {
+                    Value::Null
+                } else {
+                    json!(self.block_header(number))
+                }
+            }
+            "eth_call" => {
+                let (from, to, data) = call_params(params.first())?;
+                if to != Some(self.market.address) {
+                    return Ok(json!("0x"));
+                }
+                let mut market = self.market.clone();
+                let (output, _) = market.call(from, &data).map_err(revert)?;
+                json!(Bytes::from(output))
+            }
+            "eth_estimateGas" => {
+                let (from, to, data) = call_params(params.first())?;
+                if to == Some(self.market.address) {
+                    self.market.clone().call(from, &data).map_err(revert)?;
+                }
+                json!(U256::from(GAS))
+            }
+            "eth_sendTransaction" => {
+                let (from, to, data) = call_params(params.first())?;
+                let nonce = self.nonces.get(&from).copied().unwrap_or(0);
+                let mut bytes = from.as_bytes().to_vec();
+                bytes.extend(nonce.to_be_bytes());
+                bytes.extend(&data);
+                json!(self.send(H256(keccak256(&bytes)), from, to, data))
+            }
+            "eth_sendRawTransaction" => {
+                let raw = hex_bytes(params.first())?;
+                let (tx, sig) = TypedTransaction::decode_signed(&Rlp::new(&raw))
+                    .map_err(|_| invalid("invalid transaction"))?;
+                let from = sig
+                    .recover(tx.sighash())
+                    .map_err(|_| invalid("invalid signature"))?;
+                let to = tx.to().and_then(|to| to.as_address()).copied();
+                let data = tx.data().map(|d| d.to_vec()).unwrap_or_default();
+                json!(self.send(H256(keccak256(&raw)), from, to, data))
+            }
+            "eth_getTransactionByHash" => {
+                let hash = hash(params.first())?;
+                self.transactions.get(&hash).cloned().unwrap_or(Value::Null)
+            }
+            "eth_getTransactionReceipt" => {
+                let hash = hash(params.first())?;
+                self.receipts
+                    .get(&hash)
+                    .map(|r| json!(r))
+                    .unwrap_or(Value::Null)
+            }
+            "eth_getLogs" => json!(self.logs(params.first())),
+            _ => return Err((-32601, format!("method {} not found", method), None)),
+        };
+        Ok(result)
+    }
+
+    /// mine the transaction in a new block, reverted transaction has failed status
+    fn send(&mut self, hash: H256, from: Address, to: Option<Address>, data: Vec<u8>) -> H256 {
+        let nonce = self.nonces.entry(from).or_default();
+        *nonce += 1;
+        self.block += 1;
+
+        let block_hash = block_hash(self.block);
+        let (status, logs) = match to {
+            Some(to) if to == self.market.address => match self.market.call(from, &data) {
+                Ok((_, events)) => (1u64, events),
+                Err(err) => {
+                    tracing::info!("transaction reverted: {}", err);
+                    (0, vec![])
+                }
+            },
+            _ => (1, vec![]),
+        };
+
+        let logs: Vec<Log> = logs
+            .iter()
+            .enumerate()
+            .map(|(i, event)| Log {
+                address: self.market.address,
+                topics: vec![self.topic(event)],
+                data: encode(&event.params).into(),
+                block_hash: Some(block_hash),
+                block_number: Some(self.block.into()),
+                transaction_hash: Some(hash),
+                transaction_index: Some(U64::zero()),
+                log_index: Some((self.logs.len() + i).into()),
+                removed: Some(false),
+                ..Default::default()
+            })
+            .collect();
+        self.logs.extend(logs.iter().cloned());
+
+        let receipt = TransactionReceipt {
+            transaction_hash: hash,
+            transaction_index: U64::zero(),
+            block_hash: Some(block_hash),
+            block_number: Some(self.block.into()),
+            from,
+            to,
+            cumulative_gas_used: GAS.into(),
+            gas_used: Some(GAS.into()),
+            logs,
+            status: Some(status.into()),
+            effective_gas_price: Some(GAS_PRICE.into()),
+            ..Default::default()
+        };
+        self.receipts.insert(hash, receipt);
+        self.transactions.insert(
+            hash,
+            json!({
+                "hash": hash,
+                "from": from,
+                "to": to,
+                "input": Bytes::from(data),
+                "nonce": U256::from(*self.nonces.get(&from).unwrap_or(&1) - 1),
+                "blockHash": block_hash,
+                "blockNumber": U64::from(self.block),
+                "transactionIndex": U64::zero(),
+                "value": U256::zero(),
+                "gas": U256::from(GAS),
+                "gasPrice": U256::from(GAS_PRICE),
+            }),
+        );
+
+        hash
+    }
+
+    fn topic(&self, event: &Event) -> H256 {
+        self.market
+            .abi
+            .event(event.name)
+            .map(|e| e.signature())
+            .unwrap_or_default()
+    }
+
+    fn block_header(&self, number: u64) -> Block<H256> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Block {
+            hash: Some(block_hash(number)),
+            parent_hash: if number > 0 {
+                block_hash(number - 1)
+            } else {
+                H256::zero()
+            },
+            number: Some(number.into()),
+            timestamp: timestamp.into(),
+            gas_limit: (GAS * 30).into(),
+            base_fee_per_gas: Some(GAS_PRICE.into()),
+            ..Default::default()
+        }
+    }
+
+    /// logs of the filter: fromBlock, toBlock, address and the first topic
+    fn logs(&self, filter: Option<&Value>) -> Vec<Log> {
+        let filter = filter.cloned().unwrap_or(Value::Null);
+        let from = block_number(filter.get("fromBlock"), self.block);
+        let to = block_number(filter.get("toBlock"), self.block);
+        let addresses = values(filter.get("address"));
+        let topics = values(filter.get("topics").and_then(|t| t.get(0)));
+
+        self.logs
+            .iter()
+            .filter(|log| {
+                let block = log.block_number.map(|b| b.as_u64()).unwrap_or(0);
+                block >= from
+                    && block <= to
+                    && (addresses.is_empty() || addresses.contains(&json!(log.address)))
+                    && (topics.is_empty() || topics.contains(&json!(log.topics[0])))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn block_hash(number: u64) -> H256 {
+    H256(keccak256(number.to_be_bytes()))
+}
+
+fn block_number(value: Option<&Value>, latest: u64) -> u64 {
+    match value.and_then(|v| v.as_str()) {
+        Some("earliest") => 0,
+        Some(n) if n.starts_with("0x") => u64::from_str_radix(&n[2..], 16).unwrap_or(latest),
+        _ => latest,
+    }
+}
+
+/// a value or an array of values, lowercase to compare
+fn values(value: Option<&Value>) -> Vec<Value> {
+    let values = match value {
+        Some(Value::Array(values)) => values.clone(),
+        Some(Value::String(v)) => vec![Value::String(v.clone())],
+        _ => vec![],
+    };
+    values
+        .into_iter()
+        .filter_map(|v| v.as_str().map(|s| json!(s.to_lowercase())))
+        .collect()
+}
+
+fn invalid(message: &str) -> RpcError {
+    (-32602, message.to_owned(), None)
+}
+
+/// the execution reverted error, data is Error(string)
+fn revert(reason: String) -> RpcError {
+    let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+    data.extend(encode(&[Token::String(reason.clone())]));
+    (
+        3,
+        format!("execution reverted: {}", reason),
+        Some(json!(Bytes::from(data))),
+    )
+}
+
+fn address(value: Option<&Value>) -> Result<Address, RpcError> {
+    value
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid("invalid address"))
+}
+
+fn hash(value: Option<&Value>) -> Result<H256, RpcError> {
+    value
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid("invalid hash"))
+}
+
+fn hex_bytes(value: Option<&Value>) -> Result<Vec<u8>, RpcError> {
+    value
+        .and_then(|v| v.as_str())
+        .and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+        .ok_or_else(|| invalid("invalid bytes"))
+}
+
+/// from, to and data (or input) of the call object
+fn call_params(value: Option<&Value>) -> Result<(Address, Option<Address>, Vec<u8>), RpcError> {
+    let value = value.ok_or_else(|| invalid("missing call"))?;
+    let from = address(value.get("from")).unwrap_or_default();
+    let to = address(value.get("to")).ok();
+    let data = hex_bytes(value.get("data").or(value.get("input"))).unwrap_or_default();
+    Ok((from, to, data))
+}
+
+/// serve the JSON-RPC at the address
+pub async fn serve(chain: Chain, addr: SocketAddr) -> std::io::Result<()> {
+    let app = Router::new()
+        .route("/", post(rpc))
+        .layer(CorsLayer::permissive())
+        .with_state(Arc::new(Mutex::new(chain)));
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await
+}
+
+async fn rpc(State(chain): State<Arc<Mutex<Chain>>>, Json(request): Json<Value>) -> Json<Value> {
+    let mut chain = chain.lock().unwrap();
+    Json(chain.handle(request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpc_works() {
+        let game = Address::repeat_byte(9);
+        let player = Address::repeat_byte(1);
+        let mut chain = Chain::new(31337, Market::new(game, 4, 100000).unwrap());
+
+        let create = chain.market.abi.functions_by_name("createRoom").unwrap()[0]
+            .encode_input(&[
+                Token::Uint(0.into()),
+                Token::Bool(false),
+                Token::Address(player),
+                Token::FixedBytes(vec![0u8; 32]),
+            ])
+            .unwrap();
+        let call = json!({ "from": player, "to": game, "data": Bytes::from(create) });
+
+        let res = chain.handle(
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_sendTransaction", "params": [call] }),
+        );
+        let hash = res["result"].clone();
+        let res = chain
+            .handle(json!({ "id": 2, "method": "eth_getTransactionReceipt", "params": [hash] }));
+        assert_eq!(res["result"]["status"], json!("0x1"));
+        assert_eq!(res["result"]["logs"].as_array().unwrap().len(), 1);
+
+        let topic = chain.market.abi.event("CreateRoom").unwrap().signature();
+        let filter = json!({ "fromBlock": "0x0", "address": game, "topics": [topic] });
+        let res = chain.handle(json!([{ "id": 3, "method": "eth_getLogs", "params": [filter] }, { "id": 4, "method": "eth_blockNumber" }]));
+        assert_eq!(res[0]["result"].as_array().unwrap().len(), 1);
+        assert_eq!(res[1]["result"], json!("0x1"));
+
+        // join twice is reverted with the contract error code
+        let join = chain
+            .market
+            .abi
+            .function("joinRoom")
+            .unwrap()
+            .encode_input(&[
+                Token::Uint(100000.into()),
+                Token::Address(player),
+                Token::FixedBytes(vec![0u8; 32]),
+            ])
+            .unwrap();
+        let call = json!({ "from": player, "to": game, "data": Bytes::from(join) });
+        let res =
+            chain.handle(json!({ "id": 5, "method": "eth_call", "params": [call, "latest"] }));
+        assert_eq!(res["error"]["message"], json!("execution reverted: RM03"));
+
+        let res =
+            chain.handle(json!({ "id": 6, "method": "eth_sendTransaction", "params": [call] }));
+        let res = chain.handle(
+            json!({ "id": 7, "method": "eth_getTransactionReceipt", "params": [res["result"]] }),
+        );
+        assert_eq!(res["result"]["status"], json!("0x0"));
+    }
+}
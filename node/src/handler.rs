@@ -1,15 +1,16 @@
-use rand::Rng;
-use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
 use std::collections::HashMap;
 use std::time::Instant;
 use z4_engine::{
-    address_hex, hex_address, json, simple_game_result, Address, DefaultParams, Error,
+    address_hex, hex_address, json, Address, DefaultParams, Error,
     HandleResult, Handler, PeerId, Result, RoomId, Task, Tasks, Value,
 };
-use serde::Serialize;
+use ethers_core::k256::ecdsa::SigningKey;
+use aca_core::{
+    game_result, move_message, standings, verify_move, GameState, Operation, Spawner,
+};
+
+use crate::threshold::{sign_result, signer_from_env};
 
-const BOARD_SIZE_I: usize = 14;
-const BOARD_SIZE_J: usize = 21;
 const INIT_POSITIONS: [(usize, usize); 4] = [(0, 0), (13, 0), (0, 20), (13, 20)];
 const TIMEOUT: u128 = 300;
 const CAKE_TIMEOUT: u64 = 4;
@@ -21,16 +22,45 @@ pub struct Player {
     position: (usize, usize),
     score: u32,
     timeout: Instant,
+    // last accepted move sequence
+    seq: u64,
 }
 
-#[derive(Serialize)]
-pub enum Operation {
-    // player, position
-    Move(Address, usize, usize),
-    // index, position
-    CakeCreated(u32, usize, usize),
-    // index
-    CakeMissed(u32),
+/// Append-only operations, every operation extends the hash chain:
+/// head = keccak256(head || operation), start from keccak256(room || keccak256(seed)).
+pub struct OperationLog {
+    // state after all operations, same rules as the proof
+    state: GameState,
+    operations: Vec<Operation>,
+}
+
+impl OperationLog {
+    fn new(room: RoomId, seed: &[u8; 32], players: usize) -> Self {
+        Self {
+            state: GameState::new(room, seed, players),
+            operations: vec![],
+        }
+    }
+
+    fn head(&self) -> [u8; 32] {
+        self.state.head
+    }
+
+    /// apply and append the operation, an invalid one is not appended
+    /// and the head is not changed
+    fn push(
+        &mut self,
+        room: RoomId,
+        players: &[Address],
+        peers: &[Address],
+        op: Operation,
+    ) -> std::result::Result<(), &'static str> {
+        let mut state = self.state.clone();
+        state.apply(room, players, peers, &op)?;
+        self.state = state;
+        self.operations.push(op);
+        Ok(())
+    }
 }
 
 pub struct Cake {
@@ -40,12 +70,24 @@ pub struct Cake {
 }
 
 pub struct GameHandler {
-    prng: ChaChaRng,
+    room: RoomId,
+    seed: [u8; 32],
+    players: Vec<Address>,
+    // the peer keys of players, signed the moves
+    peers: Vec<Address>,
+    // board and cakes from the seed, same as the practice
+    spawner: Spawner,
     board: Vec<Vec<f32>>,
     accounts: HashMap<PeerId, (String, Player)>,
     alive_cakes: Vec<Cake>,
     cakes: Vec<Cake>,
-    operations: Vec<Operation>,
+    operations: OperationLog,
+    // sequencer key, signs the result for threshold settlement
+    signer: Option<SigningKey>,
+    // the game started at
+    started_at: Instant,
+    // missed cakes of all players
+    missed: u32,
 }
 
 impl GameHandler {
@@ -69,18 +111,37 @@ impl GameHandler {
         players
     }
 
-    // TODO over game
-    fn over(&self) -> (Vec<u8>, Vec<u8>) {
-        let mut players: Vec<(Address, u32)> = self
-            .accounts
+    /// scores in the order of players
+    fn scores(&self) -> Vec<u32> {
+        self.players
             .iter()
-            .map(|(_, (account, player))| (hex_address(account).unwrap(), player.score))
-            .collect();
-        players.sort_by(|(_, sa), (_, sb)| sb.cmp(sa));
-        let winners: Vec<Address> = players.iter().map(|(a, _s)| *a).collect();
+            .map(|player| {
+                let account = address_hex(player);
+                self.accounts
+                    .values()
+                    .find(|(aid, _)| *aid == account)
+                    .map(|(_, p)| p.score)
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
 
-        let rank = simple_game_result(&winners);
-        let proof = vec![];
+    /// the result and the sequencer signed proof, see `threshold`
+    fn over(&self) -> (Vec<u8>, Vec<u8>) {
+        let scores = self.scores();
+        let rank = game_result(
+            self.room,
+            &self.players,
+            &self.peers,
+            &scores,
+            self.operations.head(),
+            self.seed,
+        );
+        let proof = self
+            .signer
+            .as_ref()
+            .and_then(|key| sign_result(key, self.room, &rank, self.operations.head()))
+            .unwrap_or_default();
 
         (rank, proof)
     }
@@ -121,7 +182,16 @@ impl Task for CakeTask {
                 if let Some(next) = clears.pop() {
                     let cake = state.alive_cakes.remove(next);
                     eaten_response(&mut results, cake.index, Default::default(), 0);
-                    state.operations.push(Operation::CakeMissed(cake.index));
+                    state
+                        .operations
+                        .push(
+                            state.room,
+                            &state.players,
+                            &state.peers,
+                            Operation::CakeMissed(cake.index),
+                        )
+                        .map_err(|_| Error::Params)?;
+                    state.missed += 1;
                     state.cakes.push(cake);
                 } else {
                     break;
@@ -132,7 +202,7 @@ impl Task for CakeTask {
                 // over game
                 let (data, proof) = state.over();
                 results.over(data, proof);
-                over_response(&mut results);
+                over_response(&mut results, state);
             }
         }
 
@@ -140,8 +210,7 @@ impl Task for CakeTask {
             self.0 += 1;
 
             // random cake postion
-            let i: usize = state.prng.gen_range(0..14);
-            let j: usize = state.prng.gen_range(0..21);
+            let (i, j) = state.spawner.cake();
 
             // create new cake
             let index = self.0 as u32;
@@ -155,9 +224,20 @@ impl Task for CakeTask {
 
             // broadcast
             cake_response(&mut results, index, position);
-            state.operations.push(Operation::CakeCreated(index, position.0, position.1));
+            state
+                .operations
+                .push(
+                    state.room,
+                    &state.players,
+                    &state.peers,
+                    Operation::CakeCreated(index, position.0, position.1),
+                )
+                .map_err(|_| Error::Params)?;
         }
 
+        // broadcast the operations commitment, players can check it in the final result
+        head_response(&mut results, state.operations.operations.len(), state.operations.head());
+
         Ok(results)
     }
 }
@@ -169,7 +249,7 @@ impl Handler for GameHandler {
     async fn create(
         peers: &[(Address, PeerId, [u8; 32])],
         _params: Vec<u8>,
-        _rid: RoomId,
+        rid: RoomId,
         seed: [u8; 32]
     ) -> (Self, Tasks<Self>) {
         let timeout = Instant::now();
@@ -185,31 +265,33 @@ impl Handler for GameHandler {
                             position: INIT_POSITIONS[i],
                             score: 0,
                             timeout,
+                            seq: 0,
                         },
                     ),
                 )
             })
             .collect();
+        let players: Vec<Address> = peers.iter().map(|(account, _, _)| *account).collect();
+        let peers = peers.iter().map(|(_, peer, _)| Address::from(peer.0)).collect();
 
-        // TODO prove rng
-        let mut prng = ChaChaRng::from_seed(seed);
-
-        let board = (0..BOARD_SIZE_I)
-            .map(|_i| {
-                (0..BOARD_SIZE_J)
-                    .map(|_j| prng.gen_range(-0.1..0.1))
-                    .collect()
-            })
-            .collect();
+        let mut spawner = Spawner::new(seed);
+        let board = spawner.board();
 
         (
             Self {
-                prng,
+                room: rid,
+                seed,
+                players,
+                peers,
+                spawner,
                 board,
                 accounts,
                 alive_cakes: vec![],
                 cakes: vec![],
-                operations: vec![],
+                operations: OperationLog::new(rid, &seed, players.len()),
+                signer: signer_from_env(),
+                started_at: Instant::now(),
+                missed: 0,
             },
             vec![Box::new(CakeTask(0))],
         )
@@ -252,18 +334,38 @@ fn do_move(
     player: PeerId,
     params: DefaultParams,
 ) -> Result<HandleResult<DefaultParams>> {
-    if params.0.len() != 2 {
+    if params.0.len() != 4 {
         return Err(Error::Params);
     }
     let x = params.0[0].as_u64().unwrap_or(0) as usize;
     let y = params.0[1].as_u64().unwrap_or(0) as usize;
+    let seq = params.0[2].as_u64().unwrap_or(0);
+    let signature = hex::decode(params.0[3].as_str().unwrap_or("").trim_start_matches("0x"))
+        .map_err(|_| Error::Params)?;
     let position = (x, y);
 
-    // TODO Check new position is valid
     let (aid, p) = handler.accounts.get_mut(&player).unwrap(); // safe
+
+    // the move must be signed by the player peer key, and not replayed
+    let message = move_message(handler.room, seq, x, y);
+    if seq <= p.seq || !verify_move(Address::from(player.0), &message, &signature) {
+        return Err(Error::Params);
+    }
+    let account = aid.clone();
+
+    // rejected before any broadcast when the rules refuse it, as out of the board
+    handler
+        .operations
+        .push(
+            handler.room,
+            &handler.players,
+            &handler.peers,
+            Operation::Move(hex_address(&account).unwrap(), x, y, seq, signature),
+        )
+        .map_err(|_| Error::Params)?;
+    p.seq = seq;
     p.position.0 = position.0;
     p.position.1 = position.1;
-    let account = aid.clone();
 
     //  check if eaten
     let mut clears: Vec<usize> = vec![];
@@ -274,8 +376,7 @@ fn do_move(
     }
 
     let mut results = HandleResult::default();
-    move_response(&mut results, account.clone(), position);
-    handler.operations.push(Operation::Move(hex_address(account).unwrap(), position.0, position.1));
+    move_response(&mut results, account.clone(), position, seq);
 
     if !clears.is_empty() {
         loop {
@@ -293,21 +394,28 @@ fn do_move(
             // over game
             let (data, proof) = handler.over();
             results.over(data, proof);
-            over_response(&mut results);
+            over_response(&mut results, handler);
         }
     }
 
     Ok(results)
 }
 
+/// the accepted position with the move sequence, players reconcile their predicted moves by it
 fn move_response(
     results: &mut HandleResult<DefaultParams>,
     account: String,
     position: (usize, usize),
+    seq: u64,
 ) {
     results.add_all(
         "moved",
-        DefaultParams(vec![account.into(), position.0.into(), position.1.into()]),
+        DefaultParams(vec![
+            account.into(),
+            position.0.into(),
+            position.1.into(),
+            seq.into(),
+        ]),
     );
 }
 
@@ -330,6 +438,31 @@ fn eaten_response(
     );
 }
 
-fn over_response(results: &mut HandleResult<DefaultParams>) {
-    results.add_all("over", DefaultParams(vec![]));
+/// the standings [rank, account, score, bot], cakes missed, game seconds, the proof system,
+/// and the operations count and head of the result
+fn over_response(results: &mut HandleResult<DefaultParams>, handler: &GameHandler) {
+    let standings: Vec<Value> = standings(&handler.players, &handler.scores())
+        .into_iter()
+        .map(|(rank, player, score)| json!((rank, address_hex(&player), score, false)))
+        .collect();
+    let proof = if handler.signer.is_some() { "threshold" } else { "" };
+
+    results.add_all(
+        "over",
+        DefaultParams(vec![
+            standings.into(),
+            handler.missed.into(),
+            handler.started_at.elapsed().as_secs().into(),
+            proof.into(),
+            handler.operations.operations.len().into(),
+            format!("0x{}", hex::encode(handler.operations.head())).into(),
+        ]),
+    );
+}
+
+fn head_response(results: &mut HandleResult<DefaultParams>, count: usize, head: [u8; 32]) {
+    results.add_all(
+        "head",
+        DefaultParams(vec![count.into(), format!("0x{}", hex::encode(head)).into()]),
+    );
 }
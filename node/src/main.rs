@@ -1,6 +1,7 @@
 use z4_engine::{Config, Engine};
 
 mod handler;
+mod threshold;
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,68 @@
+//! Sequencer-signed room results for `RoomMarket.overRoomWithThreshold`,
+//! rooms can be settled without zk proof.
+//!
+//! The sequencer signs the EIP-191 personal message of
+//! keccak256(abi.encodePacked(uint256 room, keccak256(data), bytes32 head)),
+//! the proof is head || signature (r, s, v).
+
+use aca_core::result_hash;
+use ethers_core::k256::ecdsa::SigningKey;
+
+/// operations head (32 bytes) and signature (65 bytes)
+pub const PROOF_LEN: usize = 97;
+
+/// the signer key of the sequencer, from the `SECRET_KEY` env
+pub fn signer_from_env() -> Option<SigningKey> {
+    let secret = std::env::var("SECRET_KEY").ok()?;
+    // the env template has comments after the key
+    let secret = secret.split_whitespace().next()?;
+    let bytes = hex::decode(secret.trim_start_matches("0x")).ok()?;
+    SigningKey::from_slice(&bytes).ok()
+}
+
+/// sign the room result, return the proof for `overRoomWithThreshold`
+pub fn sign_result(key: &SigningKey, room: u64, data: &[u8], head: [u8; 32]) -> Option<Vec<u8>> {
+    let hash = result_hash(room, data, head);
+    let (sig, recid) = key.sign_prehash_recoverable(hash.as_bytes()).ok()?;
+
+    let mut proof = Vec::with_capacity(PROOF_LEN);
+    proof.extend(head);
+    proof.extend(sig.to_bytes());
+    proof.push(recid.to_byte() + 27);
+    Some(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aca_core::result_signer;
+    use ethers_core::{types::Address, utils::secret_key_to_address};
+
+    /// check the proof is signed by the sequencer for the room result, as the market
+    fn verify_result(sequencer: Address, room: u64, data: &[u8], proof: &[u8]) -> bool {
+        let mut head = [0u8; 32];
+        head.copy_from_slice(&proof[..32]);
+        result_signer(room, data, head, &proof[32..]) == Some(sequencer)
+    }
+
+    #[test]
+    fn threshold_result_works() {
+        let key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let sequencer = secret_key_to_address(&key);
+        let data = b"room result".to_vec();
+
+        let proof = sign_result(&key, 100001, &data, [1u8; 32]).unwrap();
+        assert_eq!(proof.len(), PROOF_LEN);
+        assert!(verify_result(sequencer, 100001, &data, &proof));
+
+        // other room, result or signer
+        assert!(!verify_result(sequencer, 100002, &data, &proof));
+        assert!(!verify_result(sequencer, 100001, b"other result", &proof));
+        assert!(!verify_result(Address::zero(), 100001, &data, &proof));
+
+        // other operations head
+        let mut forged = proof.clone();
+        forged[0] = 2;
+        assert!(!verify_result(sequencer, 100001, &data, &forged));
+    }
+}
@@ -0,0 +1,128 @@
+//! Server bot players, they fill the empty seats when the game starts, and
+//! move on the server tick with signed moves like the players.
+
+use aca_core::{bot_account, move_message, BOARD_SIZE_I, BOARD_SIZE_J};
+use ethers_core::{
+    k256::ecdsa::SigningKey,
+    types::Address,
+    utils::{keccak256, secret_key_to_address},
+};
+use rand::Rng;
+use serde_json::Value;
+use z4_pozk::types::PeerId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// chance to move on the tick
+    fn active(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 0.8,
+            Difficulty::Hard => 1.0,
+        }
+    }
+
+    /// chance to step to the nearest cake, otherwise a random step
+    fn greedy(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.3,
+            Difficulty::Normal => 0.7,
+            Difficulty::Hard => 1.0,
+        }
+    }
+}
+
+/// `AI_PLAYERS`: max bots in a room, default 0 (no bots),
+/// `AI_DIFFICULTY`: easy, normal (default) or hard
+pub fn config() -> (usize, Difficulty) {
+    let number = std::env::var("AI_PLAYERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let difficulty = match std::env::var("AI_DIFFICULTY").as_deref() {
+        Ok("easy") => Difficulty::Easy,
+        Ok("hard") => Difficulty::Hard,
+        _ => Difficulty::Normal,
+    };
+    (number, difficulty)
+}
+
+pub struct Bot {
+    pub account: Address,
+    pub peer: PeerId,
+    key: SigningKey,
+    seq: u64,
+}
+
+impl Bot {
+    pub fn new(index: u8) -> Self {
+        let key = SigningKey::random(&mut rand::thread_rng());
+        let peer = PeerId(secret_key_to_address(&key).0);
+
+        Self {
+            account: bot_account(index),
+            peer,
+            key,
+            seq: 0,
+        }
+    }
+
+    /// the move params signed by the bot peer key, same as the players
+    pub fn sign_move(&mut self, room: u64, x: usize, y: usize) -> Option<Vec<Value>> {
+        let seq = self.seq + 1;
+        let hash = keccak256(move_message(room, seq, x, y));
+        let (sig, recid) = self.key.sign_prehash_recoverable(&hash).ok()?;
+        self.seq = seq;
+
+        let mut signature = sig.to_bytes().to_vec();
+        signature.push(recid.to_byte() + 27);
+        Some(vec![x.into(), y.into(), seq.into(), hex::encode(signature).into()])
+    }
+}
+
+/// next step of the bot, one cell to the nearest cake or random
+pub fn next_step(
+    difficulty: Difficulty,
+    position: (usize, usize),
+    cakes: &[(usize, usize)],
+) -> Option<(usize, usize)> {
+    let mut rng = rand::thread_rng();
+    if !rng.gen_bool(difficulty.active()) {
+        return None;
+    }
+
+    let (i, j) = position;
+    let nearest = cakes
+        .iter()
+        .min_by_key(|(ci, cj)| ci.abs_diff(i) + cj.abs_diff(j));
+    if let Some((ci, cj)) = nearest.filter(|_| rng.gen_bool(difficulty.greedy())) {
+        if *ci != i {
+            return Some((if *ci > i { i + 1 } else { i - 1 }, j));
+        }
+        if *cj != j {
+            return Some((i, if *cj > j { j + 1 } else { j - 1 }));
+        }
+        return None;
+    }
+
+    let mut cells = vec![];
+    if i > 0 {
+        cells.push((i - 1, j));
+    }
+    if i < BOARD_SIZE_I - 1 {
+        cells.push((i + 1, j));
+    }
+    if j > 0 {
+        cells.push((i, j - 1));
+    }
+    if j < BOARD_SIZE_J - 1 {
+        cells.push((i, j + 1));
+    }
+    Some(cells[rng.gen_range(0..cells.len())])
+}
@@ -0,0 +1,76 @@
+//! Reads of the room market contract, the same `RPC_ENDPOINTS` and
+//! `ROOM_MARKET` (or `GAMES`) of the node env.
+
+use ethers_core::abi::{parse_abi, Token};
+use serde_json::{json, Value};
+use z4_pozk::types::RoomId;
+
+const ROOMS_ABI: &[&str] = &[
+    "function rooms(uint256 roomId) view returns (bool, uint256, uint256, address, uint256, uint256, bytes, uint8)",
+];
+
+/// the first of the env list
+fn env_first(key: &str) -> Option<String> {
+    let value = std::env::var(key).ok()?;
+    value
+        .split(',')
+        .map(|v| v.trim().to_owned())
+        .find(|v| !v.is_empty())
+}
+
+/// the viewable flag of the room, not viewable when it cannot be read
+pub async fn room_viewable(room: RoomId) -> bool {
+    match rooms(room).await {
+        Ok(viewable) => viewable,
+        Err(err) => {
+            println!("Room {} viewable: {}", room, err);
+            false
+        }
+    }
+}
+
+async fn rooms(room: RoomId) -> Result<bool, String> {
+    let rpc = env_first("RPC_ENDPOINTS").unwrap_or("http://127.0.0.1:8545".to_owned());
+    let contract = env_first("ROOM_MARKET")
+        .or(env_first("GAMES"))
+        .ok_or("no ROOM_MARKET or GAMES")?;
+
+    let abi = parse_abi(ROOMS_ABI).map_err(|e| e.to_string())?;
+    let function = abi.function("rooms").map_err(|e| e.to_string())?;
+    let data = function
+        .encode_input(&[Token::Uint(room.into())])
+        .map_err(|e| e.to_string())?;
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_call",
+        "params": [{ "to": contract, "data": format!("0x{}", hex::encode(data)) }, "latest"],
+    });
+    let response: Value = reqwest::Client::new()
+        .post(&rpc)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(error) = response.get("error") {
+        return Err(error.to_string());
+    }
+
+    let output = hex::decode(
+        response["result"]
+            .as_str()
+            .unwrap_or("")
+            .trim_start_matches("0x"),
+    )
+    .map_err(|e| e.to_string())?;
+    let tokens = function.decode_output(&output).map_err(|e| e.to_string())?;
+    Ok(tokens
+        .into_iter()
+        .next()
+        .and_then(|t| t.into_bool())
+        .unwrap_or(false))
+}
@@ -1,17 +1,27 @@
 use rand::Rng;
-use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 use serde_json::{Value, json};
 use z4_pozk::types::{
-    simple_game_result, MethodValues, PeerId, RoomId, Task, Tasks,
+    MethodValues, PeerId, RoomId, Task, Tasks,
     Address, Error,HandleResult, Handler, Result, Player
 };
-use serde::Serialize;
 use chrono::prelude::Utc;
+use aca_core::{
+    game_result, is_bot, move_message, standings, verify_move, GameState, Operation, Spawner,
+    BOARD_SIZE_I, BOARD_SIZE_J,
+};
+#[cfg(feature = "risc0")]
+use aca_core::{GameInput, Operations, SegmentInput};
+
+use crate::{
+    bot::{self, Bot, Difficulty},
+    chain,
+};
+
+#[cfg(all(feature = "risc0", feature = "circuit"))]
+compile_error!("features `risc0` and `circuit` are exclusive");
 
-const BOARD_SIZE_I: usize = 14;
-const BOARD_SIZE_J: usize = 21;
 const INIT_POSITIONS: [(usize, usize); 4] = [(0, 0), (13, 0), (0, 20), (13, 20)];
 const TIMEOUT: u128 = 300;
 const CAKE_TIMEOUT: u64 = 4;
@@ -20,21 +30,176 @@ const INIT_CAKE_TIMEOUT: u64 = 10;
 const LOOP_CAKE_TIMEOUT: u64 = 5;
 
 const MAX_WAITING_TIME: i64 = 120; // 2min
+// the proof system of the result, empty when not proven. The over is sent
+// before the proving, so it is the pending proof
+#[cfg(feature = "risc0")]
+const PROOF: &str = "risc0";
+#[cfg(feature = "circuit")]
+const PROOF: &str = "circuit";
+#[cfg(not(any(feature = "risc0", feature = "circuit")))]
+const PROOF: &str = "";
+#[cfg(feature = "risc0")]
+const SEGMENT_SIZE: usize = 64;
 
 pub struct GamePlayer {
     position: (usize, usize),
     score: u32,
     timeout: Instant,
+    // last accepted move sequence
+    seq: u64,
+}
+
+/// Append-only operations with the game state they lead to. With risc0, every
+/// `SEGMENT_SIZE` operations are proven as a segment while the game is running,
+/// so only the rest operations need proving when the game is over.
+pub struct OperationLog {
+    room: RoomId,
+    players: Vec<Address>,
+    peers: Vec<Address>,
+    // operations count
+    count: usize,
+    // state after all operations
+    state: GameState,
+    // state at the start of the rest operations
+    #[cfg(feature = "risc0")]
+    start: GameState,
+    // operations after the last segment
+    operations: Vec<Operation>,
+    #[cfg(feature = "risc0")]
+    segments: Vec<tokio::task::JoinHandle<std::result::Result<host::Receipt, ()>>>,
+}
+
+impl OperationLog {
+    fn new(room: RoomId, seed: &[u8; 32]) -> Self {
+        let state = GameState::new(room, seed, 0);
+
+        Self {
+            room,
+            players: vec![],
+            peers: vec![],
+            count: 0,
+            #[cfg(feature = "risc0")]
+            start: state.clone(),
+            state,
+            operations: vec![],
+            #[cfg(feature = "risc0")]
+            segments: vec![],
+        }
+    }
+
+    /// add participant before any operation
+    fn join(&mut self, account: Address, peer: Address, seed: &[u8; 32]) {
+        self.players.push(account);
+        self.peers.push(peer);
+        self.state = GameState::new(self.room, seed, self.players.len());
+        #[cfg(feature = "risc0")]
+        {
+            self.start = self.state.clone();
+        }
+    }
+
+    fn head(&self) -> [u8; 32] {
+        self.state.head
+    }
+
+    /// apply and append the operation, an invalid one is not appended
+    /// and the head is not changed
+    fn push(&mut self, op: Operation) -> std::result::Result<(), &'static str> {
+        let mut state = self.state.clone();
+        state.apply(self.room, &self.players, &self.peers, &op)?;
+        self.state = state;
+        self.operations.push(op);
+        self.count += 1;
+
+        #[cfg(feature = "risc0")]
+        if self.operations.len() == SEGMENT_SIZE {
+            let start = std::mem::replace(&mut self.start, self.state.clone());
+            let input = SegmentInput {
+                room: self.room,
+                players: self.players.clone(),
+                peers: self.peers.clone(),
+                start,
+                operations: Operations::packed(&self.players, std::mem::take(&mut self.operations)),
+            };
+
+            self.segments.push(tokio::task::spawn_blocking(move || {
+                host::prove_segment(&input)
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// wait for the running segments, and prove the whole game with them
+    #[cfg(feature = "risc0")]
+    async fn prove(&mut self, seed: [u8; 32], rank: &[u8]) -> Result<Vec<u8>> {
+        let mut receipts = vec![];
+        for task in self.segments.drain(..) {
+            let receipt = task
+                .await
+                .map_err(|_| Error::Params)?
+                .map_err(|_| Error::Params)?;
+            receipts.push(receipt);
+        }
+
+        let mut segments = vec![];
+        for receipt in &receipts {
+            segments.push(host::segment_journal(receipt).map_err(|_| Error::Params)?);
+        }
+
+        let input = GameInput {
+            room: self.room,
+            players: self.players.clone(),
+            peers: self.peers.clone(),
+            seed,
+            segments,
+            state: self.start.clone(),
+            operations: Operations::packed(&self.players, self.operations.clone()),
+        };
+        let rank = rank.to_vec();
+
+        tokio::task::spawn_blocking(move || host::prove(&input, receipts, &rank))
+            .await
+            .map_err(|_| Error::Params)?
+            .map_err(|_| Error::Params)
+    }
+
+    /// prove the whole operations with the circuit,
+    /// the proof is the poseidon operations head || groth16 proof
+    #[cfg(feature = "circuit")]
+    async fn prove(&mut self, _seed: [u8; 32], rank: &[u8]) -> Result<Vec<u8>> {
+        let room = self.room;
+        let players = self.players.clone();
+        let operations = self.operations.clone();
+        let rank = rank.to_vec();
+
+        let (statement, proof) = tokio::task::spawn_blocking(move || {
+            let (prover, _) = circuit_keys()?;
+            prover.prove(room, &players, &operations, &rank, &mut rand::thread_rng())
+        })
+        .await
+        .map_err(|_| Error::Params)?
+        .map_err(|_| Error::Params)?;
+
+        Ok([statement.head.to_vec(), proof].concat())
+    }
+
+    #[cfg(not(any(feature = "risc0", feature = "circuit")))]
+    async fn prove(&mut self, _seed: [u8; 32], _rank: &[u8]) -> Result<Vec<u8>> {
+        Ok(vec![])
+    }
 }
 
-#[derive(Serialize)]
-pub enum Operation {
-    // player, position
-    Move(Address, usize, usize),
-    // index, position
-    CakeCreated(u32, usize, usize),
-    // index
-    CakeMissed(u32),
+/// the circuit keys from the setup artifact at `CIRCUIT_KEYS`, loaded once
+#[cfg(feature = "circuit")]
+fn circuit_keys() -> std::result::Result<&'static (circuit::Prover, circuit::Verifier), &'static str> {
+    static KEYS: std::sync::OnceLock<(circuit::Prover, circuit::Verifier)> = std::sync::OnceLock::new();
+    if let Some(keys) = KEYS.get() {
+        return Ok(keys);
+    }
+    let path = std::env::var("CIRCUIT_KEYS").unwrap_or("circuit.keys".to_owned());
+    let keys = circuit::load_keys(path)?;
+    Ok(KEYS.get_or_init(|| keys))
 }
 
 pub struct Cake {
@@ -44,15 +209,30 @@ pub struct Cake {
 }
 
 pub struct GameHandler {
-    prng: ChaChaRng,
+    room: RoomId,
+    seed: [u8; 32],
+    // board and cakes from the seed, same as the practice
+    spawner: Spawner,
     board: Vec<Vec<f32>>,
     accounts: HashMap<PeerId, (String, GamePlayer)>,
+    // the viewable flag of the room on chain
+    viewable: bool,
+    // read-only connections of the viewable room, not players
+    viewers: HashSet<PeerId>,
     alive_cakes: Vec<Cake>,
     cakes: Vec<Cake>,
-    operations: Vec<Operation>,
+    operations: OperationLog,
     overtime: i64,
     started: bool,
+    // the game started at, reset when started
+    started_at: Instant,
     over: bool,
+    // missed cakes of all players
+    missed: u32,
+    // server bot players, and the max bots number
+    bots: Vec<Bot>,
+    max_bots: usize,
+    difficulty: Difficulty,
 }
 
 impl GameHandler {
@@ -60,7 +240,10 @@ impl GameHandler {
         let mut players: Vec<Value> = self
             .accounts
             .iter()
-            .map(|(_pid, (aid, p))| json!((aid.clone(), p.position.0, p.position.1, p.score)))
+            .map(|(_pid, (aid, p))| {
+                let bot = aid.parse().map(|a| is_bot(&a)).unwrap_or(false);
+                json!((aid.clone(), p.position.0, p.position.1, p.score, bot))
+            })
             .collect();
 
         let board_s: Vec<Vec<String>> = self
@@ -75,6 +258,22 @@ impl GameHandler {
         players.push(json!(boards_string.join(",")));
         players
     }
+
+    /// fill the empty seats with bots, before any operation
+    fn fill_bots(&mut self) {
+        while self.accounts.len() < INIT_POSITIONS.len() && self.bots.len() < self.max_bots {
+            let bot = Bot::new(self.bots.len() as u8);
+            let player = GamePlayer {
+                position: INIT_POSITIONS[self.accounts.len()],
+                score: 0,
+                timeout: Instant::now(),
+                seq: 0,
+            };
+            self.accounts.insert(bot.peer, (format!("{:?}", bot.account), player));
+            self.operations.join(bot.account, Address::from(bot.peer.0), &self.seed);
+            self.bots.push(bot);
+        }
+    }
 }
 
 struct CakeTask(usize);
@@ -101,7 +300,9 @@ impl Task for CakeTask {
             let now = Utc::now().timestamp();
             if now < state.overtime {
                 // start it when over waiting time
+                state.fill_bots();
                 state.started = true;
+                state.started_at = Instant::now();
                 results.started();
             }
             return Ok(results);
@@ -125,7 +326,11 @@ impl Task for CakeTask {
                 if let Some(next) = clears.pop() {
                     let cake = state.alive_cakes.remove(next);
                     eaten_response(&mut results, cake.index, Default::default(), 0);
-                    state.operations.push(Operation::CakeMissed(cake.index));
+                    state
+                        .operations
+                        .push(Operation::CakeMissed(cake.index))
+                        .map_err(|_| Error::Params)?;
+                    state.missed += 1;
                     state.cakes.push(cake);
                 } else {
                     break;
@@ -135,7 +340,7 @@ impl Task for CakeTask {
             if state.cakes.len() == CAKE_NUMBER {
                 // over game
                 state.over = true;
-                over_response(&mut results);
+                over_response(&mut results, state);
                 results.over();
             }
         }
@@ -144,8 +349,7 @@ impl Task for CakeTask {
             self.0 += 1;
 
             // random cake postion
-            let i: usize = state.prng.gen_range(0..14);
-            let j: usize = state.prng.gen_range(0..21);
+            let (i, j) = state.spawner.cake();
 
             // create new cake
             let index = self.0 as u32;
@@ -159,7 +363,61 @@ impl Task for CakeTask {
 
             // broadcast
             cake_response(&mut results, index, position);
-            state.operations.push(Operation::CakeCreated(index, position.0, position.1));
+            state
+                .operations
+                .push(Operation::CakeCreated(index, position.0, position.1))
+                .map_err(|_| Error::Params)?;
+        }
+
+        // broadcast the operations commitment, players can check it in the final result
+        head_response(&mut results, state.operations.count, state.operations.head());
+
+        Ok(results)
+    }
+}
+
+/// Bots move on every tick, with the same checks as the players.
+struct BotTask;
+
+#[async_trait::async_trait]
+impl Task for BotTask {
+    type H = GameHandler;
+
+    fn timer(&self) -> u64 {
+        1
+    }
+
+    async fn run(
+        &mut self,
+        state: &mut Self::H,
+    ) -> Result<HandleResult<<Self::H as Handler>::Param>> {
+        let mut results = HandleResult::default();
+        if !state.started {
+            return Ok(results);
+        }
+        if state.over {
+            return Err(Error::Timeout);
+        }
+
+        let cakes: Vec<(usize, usize)> = state.alive_cakes.iter().map(|c| c.position).collect();
+        for i in 0..state.bots.len() {
+            let peer = state.bots[i].peer;
+            let position = match state.accounts.get(&peer) {
+                Some((_, p)) => p.position,
+                None => continue,
+            };
+            let next = match bot::next_step(state.difficulty, position, &cakes) {
+                Some(next) => next,
+                None => continue,
+            };
+            if let Some(params) = state.bots[i].sign_move(state.room, next.0, next.1) {
+                if check_timeout(state, &peer).is_ok() {
+                    let _ = do_move(state, peer, params, &mut results);
+                }
+            }
+            if state.over {
+                break;
+            }
         }
 
         Ok(results)
@@ -177,12 +435,13 @@ impl Handler for GameHandler {
     async fn pozk_create(
         player: Player,
         _params: Vec<u8>,
-        _room: RoomId,
+        room: RoomId,
     ) -> Option<(Self, Tasks<Self>)> {
         let new_player = GamePlayer {
             position: INIT_POSITIONS[0],
             score: 0,
             timeout: Instant::now(),
+            seq: 0,
         };
 
         let mut accounts = HashMap::new();
@@ -190,28 +449,43 @@ impl Handler for GameHandler {
         accounts.insert(player.peer, (account, new_player));
 
         let now = Utc::now().timestamp();
-        let mut prng = ChaChaRng::from_entropy();
-        let board = (0..BOARD_SIZE_I)
-            .map(|_i| {
-                (0..BOARD_SIZE_J)
-                    .map(|_j| prng.gen_range(-0.1..0.1))
-                    .collect()
-            })
-            .collect();
+        let mut seed = [0u8; 32];
+        rand::thread_rng().fill(&mut seed);
+        let mut spawner = Spawner::new(seed);
+        let board = spawner.board();
+
+        let mut operations = OperationLog::new(room, &seed);
+        operations.join(player.account, Address::from(player.peer.0), &seed);
+
+        let viewable = chain::room_viewable(room).await;
+        let (max_bots, difficulty) = bot::config();
+        let mut tasks: Tasks<Self> = vec![Box::new(CakeTask(0))];
+        if max_bots > 0 {
+            tasks.push(Box::new(BotTask));
+        }
 
         Some((
             Self {
-                prng,
+                room,
+                seed,
+                spawner,
                 board,
                 accounts,
+                viewable,
+                viewers: HashSet::new(),
                 alive_cakes: vec![],
                 cakes: vec![],
-                operations: vec![],
+                operations,
                 started: false,
+                started_at: Instant::now(),
                 over: false,
+                missed: 0,
                 overtime: now + MAX_WAITING_TIME,
+                bots: vec![],
+                max_bots,
+                difficulty,
             },
-            vec![Box::new(CakeTask(0))],
+            tasks,
         ))
     }
 
@@ -229,13 +503,17 @@ impl Handler for GameHandler {
             position: INIT_POSITIONS[i],
             score: 0,
             timeout: Instant::now(),
+            seq: 0,
         };
         let account = format!("{:?}", player.account);
+        self.viewers.remove(&player.peer);
         self.accounts.insert(player.peer, (account, new_player));
+        self.operations.join(player.account, Address::from(player.peer.0), &self.seed);
 
         let mut results = HandleResult::default();
         if self.accounts.len() == 4 {
             self.started = true;
+            self.started_at = Instant::now();
             results.started();
         }
 
@@ -243,7 +521,14 @@ impl Handler for GameHandler {
     }
 
     async fn online(&mut self, peer: PeerId) -> Result<HandleResult<Self::Param>> {
-        println!("Peer: {:?} connected =====", peer);
+        if self.accounts.contains_key(&peer) {
+            println!("Peer: {:?} connected =====", peer);
+        } else if self.viewable {
+            println!("Viewer: {:?} connected =====", peer);
+            self.viewers.insert(peer);
+        } else {
+            return Err(Error::NoPlayer);
+        }
         let mut result = HandleResult::default();
         let players_status = self.status();
 
@@ -251,61 +536,99 @@ impl Handler for GameHandler {
         Ok(result)
     }
 
+    async fn offline(&mut self, peer: PeerId) -> Result<HandleResult<Self::Param>> {
+        if self.viewers.remove(&peer) {
+            println!("Viewer: {:?} disconnected =====", peer);
+        }
+        Ok(HandleResult::default())
+    }
+
     async fn handle(
         &mut self,
         peer: PeerId,
         param: Self::Param,
     ) -> Result<HandleResult<Self::Param>> {
-        if let Some((_, p)) = self.accounts.get_mut(&peer) {
-            if p.timeout.elapsed().as_millis() < TIMEOUT {
-                return Err(Error::Timeout);
-            } else {
-                p.timeout = Instant::now();
-            }
-        } else {
+        // viewers only receive the broadcasts
+        if self.viewers.contains(&peer) {
             return Err(Error::NoPlayer);
         }
+        check_timeout(self, &peer)?;
 
         let MethodValues { method, params } = param;
+        let mut results = HandleResult::default();
         match method.as_str() {
-            "move" => do_move(&mut self, peer, params),
-            _ => Err(Error::Params),
+            "move" => do_move(self, peer, params, &mut results)?,
+            _ => return Err(Error::Params),
         }
+        Ok(results)
     }
 
     async fn prove(&mut self) -> Result<(Vec<u8>, Vec<u8>)> {
-        let mut players: Vec<(Address, u32)> = self
-            .accounts
-            .iter()
-            .map(|(_, (account, player))| (account.parse().unwrap(), player.score))
-            .collect();
-        players.sort_by(|(_, sa), (_, sb)| sb.cmp(sa));
-        let winners: Vec<Address> = players.iter().map(|(a, _s)| *a).collect();
-
-        let rank = simple_game_result(&winners);
-        let proof = vec![];
+        let state = &self.operations.state;
+        let rank = game_result(
+            self.room,
+            &self.operations.players,
+            &self.operations.peers,
+            &state.scores,
+            state.head,
+            self.seed,
+        );
+        let proof = self.operations.prove(self.seed, &rank).await?;
 
         Ok((rank, proof))
     }
 }
 
+/// players and bots cannot move faster than the timeout
+fn check_timeout(handler: &mut GameHandler, peer: &PeerId) -> Result<()> {
+    if let Some((_, p)) = handler.accounts.get_mut(peer) {
+        if p.timeout.elapsed().as_millis() < TIMEOUT {
+            return Err(Error::Timeout);
+        } else {
+            p.timeout = Instant::now();
+        }
+        Ok(())
+    } else {
+        Err(Error::NoPlayer)
+    }
+}
+
 fn do_move(
     handler: &mut GameHandler,
     player: PeerId,
     params: Vec<Value>,
-) -> Result<HandleResult<MethodValues>> {
-    if params.len() != 2 {
+    results: &mut HandleResult<MethodValues>,
+) -> Result<()> {
+    if params.len() != 4 {
         return Err(Error::Params);
     }
     let x = params[0].as_u64().unwrap_or(0) as usize;
     let y = params[1].as_u64().unwrap_or(0) as usize;
+    let seq = params[2].as_u64().unwrap_or(0);
+    let signature = hex::decode(params[3].as_str().unwrap_or("").trim_start_matches("0x"))
+        .map_err(|_| Error::Params)?;
     let position = (x, y);
+    if x >= BOARD_SIZE_I || y >= BOARD_SIZE_J {
+        return Err(Error::Params);
+    }
 
-    // TODO Check new position is valid
-    let (aid, p) = handler.accounts.get_mut(&player).unwrap(); // safe
+    let (aid, p) = handler.accounts.get_mut(&player).ok_or(Error::NoPlayer)?;
+
+    // the move must be signed by the player peer key, and not replayed
+    let message = move_message(handler.room, seq, x, y);
+    if seq <= p.seq || !verify_move(Address::from(player.0), &message, &signature) {
+        return Err(Error::Params);
+    }
+    let account = aid.clone();
+
+    // rejected before any broadcast when the rules refuse it
+    handler
+        .operations
+        .push(Operation::Move(account.parse().unwrap(), x, y, seq, signature))
+        .map_err(|_| Error::Params)?;
+    p.seq = seq;
     p.position.0 = position.0;
     p.position.1 = position.1;
-    let account = aid.clone();
 
     //  check if eaten
     let mut clears: Vec<usize> = vec![];
@@ -315,16 +638,14 @@ fn do_move(
         }
     }
 
-    let mut results = HandleResult::default();
-    move_response(&mut results, account.clone(), position);
-    handler.operations.push(Operation::Move(account.parse().unwrap(), position.0, position.1));
+    move_response(results, account.clone(), position, seq);
 
     if !clears.is_empty() {
         loop {
             if let Some(next) = clears.pop() {
                 let cake = handler.alive_cakes.remove(next);
                 p.score += 1;
-                eaten_response(&mut results, cake.index, account.clone(), p.score);
+                eaten_response(results, cake.index, account.clone(), p.score);
                 handler.cakes.push(cake);
             } else {
                 break;
@@ -333,23 +654,25 @@ fn do_move(
 
         if handler.cakes.len() == CAKE_NUMBER {
             handler.over = true;
-            over_response(&mut results);
+            over_response(results, handler);
             results.over();
         }
     }
 
-    Ok(results)
+    Ok(())
 }
 
+/// the accepted position with the move sequence, players reconcile their predicted moves by it
 fn move_response(
     results: &mut HandleResult<MethodValues>,
     account: String,
     position: (usize, usize),
+    seq: u64,
 ) {
     results.add_all(
         MethodValues::new(
             "moved",
-            vec![account.into(), position.0.into(), position.1.into()]
+            vec![account.into(), position.0.into(), position.1.into(), seq.into()]
         )
     );
 }
@@ -377,11 +700,35 @@ fn eaten_response(
     );
 }
 
-fn over_response(results: &mut HandleResult<MethodValues>) {
+/// the standings [rank, account, score, bot], cakes missed, game seconds, the proof system,
+/// and the operations count and head of the result
+fn over_response(results: &mut HandleResult<MethodValues>, handler: &GameHandler) {
+    let state = &handler.operations.state;
+    let standings: Vec<Value> = standings(&handler.operations.players, &state.scores)
+        .into_iter()
+        .map(|(rank, player, score)| json!((rank, format!("{:?}", player), score, is_bot(&player))))
+        .collect();
+
     results.add_all(
         MethodValues::new(
             "over",
-            vec![]
+            vec![
+                standings.into(),
+                handler.missed.into(),
+                handler.started_at.elapsed().as_secs().into(),
+                PROOF.into(),
+                handler.operations.count.into(),
+                format!("0x{}", hex::encode(state.head)).into(),
+            ]
+        )
+    );
+}
+
+fn head_response(results: &mut HandleResult<MethodValues>, count: usize, head: [u8; 32]) {
+    results.add_all(
+        MethodValues::new(
+            "head",
+            vec![count.into(), format!("0x{}", hex::encode(head)).into()]
         )
     );
 }
@@ -1,5 +1,7 @@
 use z4_pozk::Engine;
 
+mod bot;
+mod chain;
 mod handler;
 
 #[tokio::main]
@@ -0,0 +1,399 @@
+//! The game rules shared by the zk guests, the host and the pozk prover.
+
+use ethers_core::{
+    abi::{encode, ParamType, Token},
+    types::{Address, Signature, H256, U256},
+    utils::{hash_message, keccak256},
+};
+use serde::{Deserialize, Serialize};
+
+mod packed;
+mod spawn;
+
+pub use packed::pack;
+pub use spawn::Spawner;
+
+pub const BOARD_SIZE_I: usize = 14;
+pub const BOARD_SIZE_J: usize = 21;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Operation {
+    // player, position, sequence, player signature
+    Move(Address, usize, usize, u64, Vec<u8>),
+    // index, position
+    CakeCreated(u32, usize, usize),
+    // index
+    CakeMissed(u32),
+}
+
+impl Operation {
+    /// canonical bytes of the operation, used for the operations hash chain
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        match self {
+            Operation::Move(player, x, y, seq, _signature) => {
+                bytes.push(0u8);
+                bytes.extend(player.as_bytes());
+                bytes.extend((*x as u32).to_be_bytes());
+                bytes.extend((*y as u32).to_be_bytes());
+                bytes.extend(seq.to_be_bytes());
+            }
+            Operation::CakeCreated(index, x, y) => {
+                bytes.push(1u8);
+                bytes.extend(index.to_be_bytes());
+                bytes.extend((*x as u32).to_be_bytes());
+                bytes.extend((*y as u32).to_be_bytes());
+            }
+            Operation::CakeMissed(index) => {
+                bytes.push(2u8);
+                bytes.extend(index.to_be_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+/// room || sequence || position, player signed keccak256(message) with the peer key
+pub fn move_message(room: u64, seq: u64, x: usize, y: usize) -> Vec<u8> {
+    let mut bytes = room.to_be_bytes().to_vec();
+    bytes.extend(seq.to_be_bytes());
+    bytes.extend((x as u32).to_be_bytes());
+    bytes.extend((y as u32).to_be_bytes());
+    bytes
+}
+
+/// check the signature is signed by the peer key
+pub fn verify_move(peer: Address, message: &[u8], signature: &[u8]) -> bool {
+    match Signature::try_from(signature) {
+        Ok(sig) => sig
+            .recover(H256(keccak256(message)))
+            .map(|signer| signer == peer)
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+/// commitment of the room participants and their peer keys
+pub fn players_hash(players: &[Address], peers: &[Address]) -> [u8; 32] {
+    let mut bytes = vec![];
+    for player in players {
+        bytes.extend(player.as_bytes());
+    }
+    for peer in peers {
+        bytes.extend(peer.as_bytes());
+    }
+    keccak256(&bytes)
+}
+
+/// The game state between operations, segments start and end at a state.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameState {
+    // operations hash chain head
+    pub head: [u8; 32],
+    // alive cakes by index: index, position
+    pub cakes: Vec<(u32, usize, usize)>,
+    // scores, same order as players
+    pub scores: Vec<u32>,
+    // last move sequences, same order as players
+    pub seqs: Vec<u64>,
+}
+
+impl GameState {
+    /// the state before any operation,
+    /// the hash chain start from keccak256(room || keccak256(seed))
+    pub fn new(room: u64, seed: &[u8; 32], players: usize) -> Self {
+        let mut bytes = room.to_be_bytes().to_vec();
+        bytes.extend(keccak256(seed));
+
+        Self {
+            head: keccak256(&bytes),
+            cakes: vec![],
+            scores: vec![0; players],
+            seqs: vec![0; players],
+        }
+    }
+
+    /// canonical commitment of the state
+    pub fn commitment(&self) -> [u8; 32] {
+        let mut bytes = self.head.to_vec();
+        bytes.extend((self.cakes.len() as u32).to_be_bytes());
+        for (index, x, y) in &self.cakes {
+            bytes.extend(index.to_be_bytes());
+            bytes.extend((*x as u32).to_be_bytes());
+            bytes.extend((*y as u32).to_be_bytes());
+        }
+        bytes.extend((self.scores.len() as u32).to_be_bytes());
+        for score in &self.scores {
+            bytes.extend(score.to_be_bytes());
+        }
+        for seq in &self.seqs {
+            bytes.extend(seq.to_be_bytes());
+        }
+        keccak256(&bytes)
+    }
+
+    /// every player has a peer key, a score and a sequence
+    fn check(&self, players: &[Address], peers: &[Address]) -> Result<(), &'static str> {
+        if peers.len() != players.len()
+            || self.scores.len() != players.len()
+            || self.seqs.len() != players.len()
+        {
+            return Err("state not match players");
+        }
+        Ok(())
+    }
+
+    /// extend the hash chain and apply the operation
+    pub fn apply(
+        &mut self,
+        room: u64,
+        players: &[Address],
+        peers: &[Address],
+        op: &Operation,
+    ) -> Result<(), &'static str> {
+        self.check(players, peers)?;
+        self.apply_checked(room, players, peers, op)
+    }
+
+    fn apply_checked(
+        &mut self,
+        room: u64,
+        players: &[Address],
+        peers: &[Address],
+        op: &Operation,
+    ) -> Result<(), &'static str> {
+        let mut bytes = self.head.to_vec();
+        bytes.extend(op.to_bytes());
+        self.head = keccak256(&bytes);
+
+        match op {
+            Operation::Move(player, x, y, seq, signature) => {
+                let i = players
+                    .iter()
+                    .position(|p| p == player)
+                    .ok_or("move from unknown player")?;
+                if *x >= BOARD_SIZE_I || *y >= BOARD_SIZE_J {
+                    return Err("position out of board");
+                }
+
+                // the move must be signed by the player registered peer key
+                let message = move_message(room, *seq, *x, *y);
+                if !verify_move(peers[i], &message, signature) {
+                    return Err("move not signed by player");
+                }
+
+                // and never replayed
+                if *seq <= self.seqs[i] {
+                    return Err("move sequence replayed");
+                }
+                self.seqs[i] = *seq;
+
+                let before = self.cakes.len();
+                self.cakes.retain(|(_, x1, y1)| x1 != x || y1 != y);
+                self.scores[i] += (before - self.cakes.len()) as u32;
+            }
+            Operation::CakeCreated(index, x, y) => {
+                if *x >= BOARD_SIZE_I || *y >= BOARD_SIZE_J {
+                    return Err("position out of board");
+                }
+                self.cakes.retain(|(i, _, _)| i != index);
+                let pos = self.cakes.partition_point(|(i, _, _)| i < index);
+                self.cakes.insert(pos, (*index, *x, *y));
+            }
+            Operation::CakeMissed(index) => {
+                self.cakes.retain(|(i, _, _)| i != index);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// apply all the operations
+    pub fn apply_all(
+        &mut self,
+        room: u64,
+        players: &[Address],
+        peers: &[Address],
+        operations: &Operations,
+    ) -> Result<(), &'static str> {
+        match operations {
+            Operations::Full(operations) => {
+                self.check(players, peers)?;
+                for op in operations {
+                    self.apply_checked(room, players, peers, op)?;
+                }
+                Ok(())
+            }
+            Operations::Packed(bytes) => self.apply_packed(room, players, peers, bytes),
+        }
+    }
+}
+
+/// Operations in the proving input.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Operations {
+    // serde operations, simple but costs more cycles
+    Full(Vec<Operation>),
+    // packed operations stream, see `pack`
+    Packed(Vec<u8>),
+}
+
+/// Proving input of a segment: apply operations from the start state.
+#[derive(Serialize, Deserialize)]
+pub struct SegmentInput {
+    pub room: u64,
+    pub players: Vec<Address>,
+    pub peers: Vec<Address>,
+    pub start: GameState,
+    pub operations: Operations,
+}
+
+/// Public output of a segment.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SegmentJournal {
+    pub room: u64,
+    // players_hash of participants and peers
+    pub players: [u8; 32],
+    // start state commitment
+    pub start: [u8; 32],
+    // end state commitment
+    pub end: [u8; 32],
+}
+
+impl SegmentJournal {
+    /// room || players || start || end, the bytes committed by the segment guest
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.room.to_be_bytes().to_vec();
+        bytes.extend(self.players);
+        bytes.extend(self.start);
+        bytes.extend(self.end);
+        bytes
+    }
+}
+
+/// Proving input of the whole game. The proven segments move the game
+/// from the initial state to `state`, then apply the rest `operations`.
+#[derive(Serialize, Deserialize)]
+pub struct GameInput {
+    // room id in the market
+    pub room: u64,
+    // all participants, in room join order
+    pub players: Vec<Address>,
+    // the registered peer key of every participant
+    pub peers: Vec<Address>,
+    // the seed of the room prng
+    pub seed: [u8; 32],
+    // journals of proven segments, in order
+    pub segments: Vec<SegmentJournal>,
+    // the state after the last segment
+    pub state: GameState,
+    // operations after the last segment
+    pub operations: Operations,
+}
+
+/// The reserved account of the server bot player, no one has its key.
+pub fn bot_account(index: u8) -> Address {
+    let mut bytes = [0u8; 20];
+    bytes[19] = index + 1;
+    Address::from(bytes)
+}
+
+pub fn is_bot(account: &Address) -> bool {
+    account.0[..19] == [0u8; 19] && account.0[19] != 0
+}
+
+/// whether the player is in the rank of `game_result`, bots and players
+/// who ate no cake win no rewards
+pub fn is_ranked(player: &Address, score: u32) -> bool {
+    !is_bot(player) && score > 0
+}
+
+/// abi.encode(uint256 room, address[] players, address[] peers, uint256[] scores, address[] rank,
+/// bytes32 head, bytes32 seed), the rank only has the `is_ranked` players. The peers are the keys
+/// that signed the moves, the contract checks them with the room peers.
+pub fn game_result(
+    room: u64,
+    players: &[Address],
+    peers: &[Address],
+    scores: &[u32],
+    head: [u8; 32],
+    seed: [u8; 32],
+) -> Vec<u8> {
+    let mut ranks: Vec<(Address, u32)> = players
+        .iter()
+        .copied()
+        .zip(scores.iter().copied())
+        .filter(|(player, score)| is_ranked(player, *score))
+        .collect();
+    ranks.sort_by(|(_, sa), (_, sb)| sb.cmp(sa));
+
+    encode(&[
+        Token::Uint(U256::from(room)),
+        Token::Array(players.iter().map(|v| Token::Address(*v)).collect()),
+        Token::Array(peers.iter().map(|v| Token::Address(*v)).collect()),
+        Token::Array(scores.iter().map(|v| Token::Uint(U256::from(*v))).collect()),
+        Token::Array(ranks.iter().map(|(v, _)| Token::Address(*v)).collect()),
+        Token::FixedBytes(head.to_vec()),
+        Token::FixedBytes(keccak256(seed).to_vec()),
+    ])
+}
+
+/// the abi types of `game_result`
+pub fn result_types() -> [ParamType; 7] {
+    [
+        ParamType::Uint(256),
+        ParamType::Array(Box::new(ParamType::Address)),
+        ParamType::Array(Box::new(ParamType::Address)),
+        ParamType::Array(Box::new(ParamType::Uint(256))),
+        ParamType::Array(Box::new(ParamType::Address)),
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+    ]
+}
+
+/// the EIP-191 digest the sequencer signs for `overRoomWithThreshold`:
+/// keccak256(abi.encodePacked(uint256 room, keccak256(data), bytes32 head))
+pub fn result_hash(room: u64, data: &[u8], head: [u8; 32]) -> H256 {
+    let mut bytes = [0u8; 32];
+    U256::from(room).to_big_endian(&mut bytes);
+    let mut message = bytes.to_vec();
+    message.extend(keccak256(data));
+    message.extend(head);
+
+    hash_message(keccak256(&message))
+}
+
+/// the signer of the room result, signature is (r, s, v)
+pub fn result_signer(room: u64, data: &[u8], head: [u8; 32], signature: &[u8]) -> Option<Address> {
+    let sig = Signature::try_from(signature).ok()?;
+    sig.recover(result_hash(room, data, head)).ok()
+}
+
+/// the final standings: (rank, player, score) in the order of `game_result`,
+/// the unranked players and bots follow with rank 0
+pub fn standings(players: &[Address], scores: &[u32]) -> Vec<(usize, Address, u32)> {
+    let mut standings: Vec<(Address, u32)> = players
+        .iter()
+        .copied()
+        .zip(scores.iter().copied())
+        .collect();
+    standings.sort_by(|(pa, sa), (pb, sb)| {
+        is_ranked(pb, *sb)
+            .cmp(&is_ranked(pa, *sa))
+            .then(is_bot(pa).cmp(&is_bot(pb)))
+            .then(sb.cmp(sa))
+    });
+
+    let mut rank = 0;
+    standings
+        .into_iter()
+        .map(|(player, score)| {
+            if !is_ranked(&player, score) {
+                (0, player, score)
+            } else {
+                rank += 1;
+                (rank, player, score)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,223 @@
+//! Cycle-optimized operations for the zk guest: players are referenced by
+//! index, positions are u8, and cakes are looked up on the board directly.
+
+use ethers_core::{types::Address, utils::keccak256};
+
+use crate::{
+    move_message, verify_move, GameState, Operation, Operations, BOARD_SIZE_I, BOARD_SIZE_J,
+};
+
+const MOVE: u8 = 0;
+const CAKE_CREATED: u8 = 1;
+const CAKE_MISSED: u8 = 2;
+
+/// tag, player index, x, y, sequence (u32), signature (65 bytes)
+const MOVE_LEN: usize = 73;
+/// tag, index (u16), x, y
+const CAKE_CREATED_LEN: usize = 5;
+/// tag, index (u16)
+const CAKE_MISSED_LEN: usize = 3;
+
+/// Pack operations into the compact stream, moves must be from the players.
+pub fn pack(players: &[Address], operations: &[Operation]) -> Result<Vec<u8>, &'static str> {
+    let mut bytes = vec![];
+    for op in operations {
+        match op {
+            Operation::Move(player, x, y, seq, signature) => {
+                let i = players
+                    .iter()
+                    .position(|p| p == player)
+                    .ok_or("move from unknown player")?;
+                if signature.len() != 65 || *seq > u32::MAX as u64 {
+                    return Err("move cannot be packed");
+                }
+                if *x >= BOARD_SIZE_I || *y >= BOARD_SIZE_J {
+                    return Err("position out of board");
+                }
+                bytes.extend([MOVE, i as u8, *x as u8, *y as u8]);
+                bytes.extend((*seq as u32).to_be_bytes());
+                bytes.extend(signature);
+            }
+            Operation::CakeCreated(index, x, y) => {
+                if *index > u16::MAX as u32 {
+                    return Err("cake cannot be packed");
+                }
+                if *x >= BOARD_SIZE_I || *y >= BOARD_SIZE_J {
+                    return Err("position out of board");
+                }
+                bytes.push(CAKE_CREATED);
+                bytes.extend((*index as u16).to_be_bytes());
+                bytes.extend([*x as u8, *y as u8]);
+            }
+            Operation::CakeMissed(index) => {
+                if *index > u16::MAX as u32 {
+                    return Err("cake cannot be packed");
+                }
+                bytes.push(CAKE_MISSED);
+                bytes.extend((*index as u16).to_be_bytes());
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+impl Operations {
+    /// packed operations if possible, otherwise the serde operations
+    pub fn packed(players: &[Address], operations: Vec<Operation>) -> Self {
+        match pack(players, &operations) {
+            Ok(bytes) => Operations::Packed(bytes),
+            Err(_) => Operations::Full(operations),
+        }
+    }
+}
+
+/// The alive cakes indexed by board cell, every lookup only touches the cakes
+/// of the cell.
+struct Board {
+    // alive cake indexes on every cell
+    cells: Vec<Vec<u16>>,
+    // cell of every alive cake, by cake index
+    cakes: Vec<Option<u16>>,
+}
+
+impl Board {
+    fn new(cakes: &[(u32, usize, usize)]) -> Self {
+        let mut board = Self {
+            cells: vec![vec![]; BOARD_SIZE_I * BOARD_SIZE_J],
+            cakes: vec![],
+        };
+        for (index, x, y) in cakes {
+            board.create(*index as usize, *x, *y);
+        }
+        board
+    }
+
+    fn create(&mut self, index: usize, x: usize, y: usize) {
+        self.miss(index);
+        let cell = x * BOARD_SIZE_J + y;
+        if self.cakes.len() <= index {
+            self.cakes.resize(index + 1, None);
+        }
+        self.cakes[index] = Some(cell as u16);
+        self.cells[cell].push(index as u16);
+    }
+
+    fn miss(&mut self, index: usize) {
+        if let Some(Some(cell)) = self.cakes.get_mut(index).map(|c| c.take()) {
+            self.cells[cell as usize].retain(|c| *c as usize != index);
+        }
+    }
+
+    /// eat all cakes on the cell, return the eaten number
+    fn eat(&mut self, x: usize, y: usize) -> u32 {
+        let eaten = std::mem::take(&mut self.cells[x * BOARD_SIZE_J + y]);
+        for index in &eaten {
+            self.cakes[*index as usize] = None;
+        }
+        eaten.len() as u32
+    }
+
+    /// alive cakes by index, same as the GameState
+    fn to_cakes(&self) -> Vec<(u32, usize, usize)> {
+        self.cakes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cell)| {
+                cell.map(|c| {
+                    let c = c as usize;
+                    (index as u32, c / BOARD_SIZE_J, c % BOARD_SIZE_J)
+                })
+            })
+            .collect()
+    }
+}
+
+impl GameState {
+    /// Apply the packed operations, same rules and hash chain as `apply`.
+    pub fn apply_packed(
+        &mut self,
+        room: u64,
+        players: &[Address],
+        peers: &[Address],
+        bytes: &[u8],
+    ) -> Result<(), &'static str> {
+        self.check(players, peers)?;
+        let mut board = Board::new(&self.cakes);
+        // canonical bytes of the operation, reused for the hash chain
+        let mut chain = Vec::with_capacity(32 + 1 + 20 + 16);
+
+        let mut i = 0;
+        while i < bytes.len() {
+            chain.clear();
+            chain.extend(self.head);
+
+            match bytes[i] {
+                MOVE => {
+                    let op = bytes.get(i..i + MOVE_LEN).ok_or("invalid packed move")?;
+                    let p = op[1] as usize;
+                    let (x, y) = (op[2] as usize, op[3] as usize);
+                    let seq = u32::from_be_bytes([op[4], op[5], op[6], op[7]]) as u64;
+                    let player = players.get(p).ok_or("move from unknown player")?;
+                    if x >= BOARD_SIZE_I || y >= BOARD_SIZE_J {
+                        return Err("position out of board");
+                    }
+
+                    chain.push(MOVE);
+                    chain.extend(player.as_bytes());
+                    chain.extend((x as u32).to_be_bytes());
+                    chain.extend((y as u32).to_be_bytes());
+                    chain.extend(seq.to_be_bytes());
+
+                    // the move must be signed by the player registered peer key
+                    if !verify_move(peers[p], &move_message(room, seq, x, y), &op[8..]) {
+                        return Err("move not signed by player");
+                    }
+
+                    // and never replayed
+                    if seq <= self.seqs[p] {
+                        return Err("move sequence replayed");
+                    }
+                    self.seqs[p] = seq;
+                    self.scores[p] += board.eat(x, y);
+                    i += MOVE_LEN;
+                }
+                CAKE_CREATED => {
+                    let op = bytes
+                        .get(i..i + CAKE_CREATED_LEN)
+                        .ok_or("invalid packed cake")?;
+                    let index = u16::from_be_bytes([op[1], op[2]]) as u32;
+                    let (x, y) = (op[3] as usize, op[4] as usize);
+                    if x >= BOARD_SIZE_I || y >= BOARD_SIZE_J {
+                        return Err("position out of board");
+                    }
+
+                    chain.push(CAKE_CREATED);
+                    chain.extend(index.to_be_bytes());
+                    chain.extend((x as u32).to_be_bytes());
+                    chain.extend((y as u32).to_be_bytes());
+
+                    board.create(index as usize, x, y);
+                    i += CAKE_CREATED_LEN;
+                }
+                CAKE_MISSED => {
+                    let op = bytes
+                        .get(i..i + CAKE_MISSED_LEN)
+                        .ok_or("invalid packed cake")?;
+                    let index = u16::from_be_bytes([op[1], op[2]]) as u32;
+
+                    chain.push(CAKE_MISSED);
+                    chain.extend(index.to_be_bytes());
+
+                    board.miss(index as usize);
+                    i += CAKE_MISSED_LEN;
+                }
+                _ => return Err("invalid packed operation"),
+            }
+
+            self.head = keccak256(&chain);
+        }
+
+        self.cakes = board.to_cakes();
+        Ok(())
+    }
+}
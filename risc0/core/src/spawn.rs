@@ -0,0 +1,33 @@
+//! The room prng from the seed, the sequencer and the practice spawn the
+//! board and the cakes with it in the same order.
+
+use rand::Rng;
+use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+use crate::{BOARD_SIZE_I, BOARD_SIZE_J};
+
+pub struct Spawner(ChaChaRng);
+
+impl Spawner {
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self(ChaChaRng::from_seed(seed))
+    }
+
+    /// heights of the board cells by rows, spawned before any cake
+    pub fn board(&mut self) -> Vec<Vec<f32>> {
+        (0..BOARD_SIZE_I)
+            .map(|_| {
+                (0..BOARD_SIZE_J)
+                    .map(|_| self.0.gen_range(-0.1..0.1))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// position of the next cake
+    pub fn cake(&mut self) -> (usize, usize) {
+        let i = self.0.gen_range(0..BOARD_SIZE_I);
+        let j = self.0.gen_range(0..BOARD_SIZE_J);
+        (i, j)
+    }
+}
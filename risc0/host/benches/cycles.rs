@@ -0,0 +1,161 @@
+//! Cycle counts of a full 10-cake, 4-player game with the serde and packed input,
+//! and the original HashMap guest as the baseline.
+//!
+//! `RISC0_DEV_MODE=1 cargo bench --bench cycles`
+
+use ethers_core::{
+    k256::ecdsa::SigningKey,
+    types::{Address, H160},
+    utils::{keccak256, secret_key_to_address},
+};
+use host::{execute, move_message, pack, GameInput, GameState, Operation, Operations};
+use methods::ACA_BASELINE_ELF;
+use risc0_zkvm::{default_executor, ExecutorEnv};
+use serde::Serialize;
+
+const ROOM: u64 = 100000;
+const CAKES: u32 = 10;
+const INIT_POSITIONS: [(usize, usize); 4] = [(0, 0), (13, 0), (0, 20), (13, 20)];
+// a cake is missed if nobody eat it in these rounds
+const CAKE_ROUNDS: usize = 12;
+
+struct BenchPlayer {
+    account: Address,
+    key: SigningKey,
+    seq: u64,
+    position: (usize, usize),
+}
+
+impl BenchPlayer {
+    fn move_to(&mut self, x: usize, y: usize) -> Operation {
+        self.seq += 1;
+        self.position = (x, y);
+        let digest = keccak256(move_message(ROOM, self.seq, x, y));
+        let (sig, recid) = self.key.sign_prehash_recoverable(&digest).unwrap();
+        let mut signature = sig.to_bytes().to_vec();
+        signature.push(recid.to_byte() + 27);
+        Operation::Move(self.account, x, y, self.seq, signature)
+    }
+
+    /// one step to the target, rows first
+    fn step(&mut self, target: (usize, usize)) -> Operation {
+        let (mut x, mut y) = self.position;
+        if x < target.0 {
+            x += 1;
+        } else if x > target.0 {
+            x -= 1;
+        } else if y < target.1 {
+            y += 1;
+        } else if y > target.1 {
+            y -= 1;
+        }
+        self.move_to(x, y)
+    }
+}
+
+/// every player walks to the cake one step per round, slow players miss some cakes
+fn full_game(players: &mut [BenchPlayer]) -> Vec<Operation> {
+    let mut operations = vec![];
+    for index in 1..=CAKES {
+        let cake = ((index as usize * 5) % 14, (index as usize * 7) % 21);
+        operations.push(Operation::CakeCreated(index, cake.0, cake.1));
+
+        let mut eaten = false;
+        for _ in 0..CAKE_ROUNDS {
+            for player in players.iter_mut() {
+                if player.position != cake {
+                    operations.push(player.step(cake));
+                }
+                if player.position == cake {
+                    eaten = true;
+                    break;
+                }
+            }
+            if eaten {
+                break;
+            }
+        }
+        if !eaten {
+            operations.push(Operation::CakeMissed(index));
+        }
+    }
+    operations
+}
+
+/// The operations of the original guest, no sequences and signatures.
+#[derive(Serialize)]
+enum BaselineOperation {
+    Move(Address, usize, usize),
+    CakeCreated(u32, usize, usize),
+    CakeMissed(u32),
+}
+
+/// input size and cycles of the original guest with the same game
+fn baseline(operations: &[Operation]) -> (usize, u64) {
+    let operations: Vec<BaselineOperation> = operations
+        .iter()
+        .map(|op| match op {
+            Operation::Move(player, x, y, _, _) => BaselineOperation::Move(*player, *x, *y),
+            Operation::CakeCreated(index, x, y) => BaselineOperation::CakeCreated(*index, *x, *y),
+            Operation::CakeMissed(index) => BaselineOperation::CakeMissed(*index),
+        })
+        .collect();
+    let size = risc0_zkvm::serde::to_vec(&operations).unwrap().len() * 4;
+
+    let env = ExecutorEnv::builder()
+        .write(&operations)
+        .unwrap()
+        .build()
+        .unwrap();
+    let info = default_executor().execute(env, ACA_BASELINE_ELF).unwrap();
+    (size, info.segments.iter().map(|s| s.cycles as u64).sum())
+}
+
+fn main() {
+    let mut players: Vec<BenchPlayer> = INIT_POSITIONS
+        .iter()
+        .enumerate()
+        .map(|(i, position)| BenchPlayer {
+            account: H160([i as u8 + 1; 20]),
+            key: SigningKey::from_slice(&[i as u8 + 1; 32]).unwrap(),
+            seq: 0,
+            position: *position,
+        })
+        .collect();
+    let operations = full_game(&mut players);
+
+    let accounts: Vec<Address> = players.iter().map(|p| p.account).collect();
+    let peers: Vec<Address> = players
+        .iter()
+        .map(|p| secret_key_to_address(&p.key))
+        .collect();
+    let seed = [7u8; 32];
+    let packed = pack(&accounts, &operations).unwrap();
+
+    println!("operations: {}", operations.len());
+    let (size, cycles) = baseline(&operations);
+    println!(
+        "{:>8}: input {:>7} bytes, {:>10} cycles, no signatures",
+        "baseline", size, cycles
+    );
+    for (name, ops) in [
+        ("serde", Operations::Full(operations.clone())),
+        ("packed", Operations::Packed(packed)),
+    ] {
+        let input = GameInput {
+            room: ROOM,
+            players: accounts.clone(),
+            peers: peers.clone(),
+            seed,
+            segments: vec![],
+            state: GameState::new(ROOM, &seed, accounts.len()),
+            operations: ops,
+        };
+        let size = risc0_zkvm::serde::to_vec(&input).unwrap().len() * 4;
+        let cycles = execute(&input, vec![]).unwrap();
+        println!(
+            "{:>8}: input {:>7} bytes, {:>10} cycles",
+            name, size, cycles
+        );
+    }
+}
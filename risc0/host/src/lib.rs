@@ -1,25 +1,112 @@
 // These constants represent the RISC-V ELF and the image ID generated by risc0-build.
 // The ELF is used for proving and the ID is used for verification.
-use methods::{
-    ACA_ZK_ELF, ACA_ZK_ID
-};
-use risc0_zkvm::{default_prover, ExecutorEnv};
+use methods::{ACA_ZK_ELF, ACA_ZK_ID};
+use segment_methods::{ACA_SEGMENT_ELF, ACA_SEGMENT_ID};
+use risc0_zkvm::{default_executor, default_prover, ExecutorEnv};
 use ethers_core::types::Address;
-use serde::Serialize;
-
-#[derive(Serialize)]
-pub enum Operation {
-    // player, position
-    Move(Address, usize, usize),
-    // index, position
-    CakeCreated(u32, usize, usize),
-    // index
-    CakeMissed(u32),
+
+pub use aca_core::{
+    bot_account, game_result, move_message, pack, players_hash, GameInput, GameState, Operation,
+    Operations, SegmentInput, SegmentJournal,
+};
+pub use risc0_zkvm::Receipt;
+
+/// Split the operations to fixed-size packed segments from the initial state,
+/// return the segments, the state after them and the rest operations.
+pub fn split(
+    room: u64,
+    players: &[Address],
+    peers: &[Address],
+    seed: &[u8; 32],
+    operations: Vec<Operation>,
+    size: usize,
+) -> Result<(Vec<SegmentInput>, GameState, Vec<Operation>), &'static str> {
+    let mut state = GameState::new(room, seed, players.len());
+    let mut segments = vec![];
+    let mut rest = vec![];
+
+    for op in operations {
+        rest.push(op);
+        if rest.len() == size {
+            let start = state.clone();
+            for op in &rest {
+                state.apply(room, players, peers, op)?;
+            }
+            segments.push(SegmentInput {
+                room,
+                players: players.to_vec(),
+                peers: peers.to_vec(),
+                start,
+                operations: Operations::Packed(pack(players, &rest)?),
+            });
+            rest.clear();
+        }
+    }
+
+    Ok((segments, state, rest))
 }
 
-pub fn prove(operations: &Vec<Operation>, output: &[u8]) -> Result<Vec<u8>, ()> {
+/// Prove a segment of operations, can run while the game is playing.
+pub fn prove_segment(input: &SegmentInput) -> Result<Receipt, ()> {
     let env = ExecutorEnv::builder()
-        .write(operations)
+        .write(input)
+        .map_err(|_| ())?
+        .build()
+        .map_err(|_| ())?;
+
+    let receipt = default_prover()
+        .prove(env, ACA_SEGMENT_ELF)
+        .map_err(|_| ())?
+        .receipt;
+    receipt.verify(ACA_SEGMENT_ID).map_err(|_| ())?;
+
+    Ok(receipt)
+}
+
+/// Journal of a proven segment
+pub fn segment_journal(receipt: &Receipt) -> Result<SegmentJournal, ()> {
+    let bytes = &receipt.journal.bytes;
+    if bytes.len() != 104 {
+        return Err(());
+    }
+
+    let mut room = [0u8; 8];
+    let mut players = [0u8; 32];
+    let mut start = [0u8; 32];
+    let mut end = [0u8; 32];
+    room.copy_from_slice(&bytes[0..8]);
+    players.copy_from_slice(&bytes[8..40]);
+    start.copy_from_slice(&bytes[40..72]);
+    end.copy_from_slice(&bytes[72..104]);
+
+    Ok(SegmentJournal {
+        room: u64::from_be_bytes(room),
+        players,
+        start,
+        end,
+    })
+}
+
+/// Execute the game guest without proving, return the total cycles.
+pub fn execute(input: &GameInput, segments: Vec<Receipt>) -> Result<u64, ()> {
+    let mut builder = ExecutorEnv::builder();
+    for receipt in segments {
+        builder.add_assumption(receipt);
+    }
+    let env = builder.write(input).map_err(|_| ())?.build().map_err(|_| ())?;
+
+    let info = default_executor().execute(env, ACA_ZK_ELF).map_err(|_| ())?;
+    Ok(info.segments.iter().map(|s| s.cycles as u64).sum())
+}
+
+/// Prove the whole game, compose with the proven segments.
+pub fn prove(input: &GameInput, segments: Vec<Receipt>, output: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut builder = ExecutorEnv::builder();
+    for receipt in segments {
+        builder.add_assumption(receipt);
+    }
+    let env = builder
+        .write(input)
         .unwrap()
         .build()
         .unwrap();
@@ -53,44 +140,312 @@ pub fn prove(operations: &Vec<Operation>, output: &[u8]) -> Result<Vec<u8>, ()>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ethers_core::{abi::{encode, Token}, types::H160};
+    use ethers_core::{
+        abi::{encode, Token},
+        k256::ecdsa::SigningKey,
+        types::H160,
+        utils::{keccak256, secret_key_to_address},
+    };
+
+    /// final head of the operations hash chain
+    fn operations_head(room: u64, seed: &[u8; 32], operations: &[Operation]) -> [u8; 32] {
+        let mut state = GameState::new(room, seed, 0);
+        for op in operations {
+            let mut bytes = state.head.to_vec();
+            bytes.extend(op.to_bytes());
+            state.head = keccak256(&bytes);
+        }
+        state.head
+    }
+
+    struct TestPlayer {
+        account: Address,
+        key: SigningKey,
+        seq: u64,
+    }
 
-    fn simple_game_result(ranks: &[Address]) -> Vec<u8> {
-        encode(&[Token::Array(
-            ranks.iter().map(|v| Token::Address(*v)).collect(),
-        )])
+    impl TestPlayer {
+        fn new(i: u8) -> Self {
+            Self {
+                account: H160([i; 20]),
+                key: SigningKey::from_slice(&[i; 32]).unwrap(),
+                seq: 0,
+            }
+        }
+
+        fn peer(&self) -> Address {
+            secret_key_to_address(&self.key)
+        }
+
+        fn move_to(&mut self, room: u64, x: usize, y: usize) -> Operation {
+            self.seq += 1;
+            let digest = keccak256(move_message(room, self.seq, x, y));
+            let (sig, recid) = self.key.sign_prehash_recoverable(&digest).unwrap();
+            let mut signature = sig.to_bytes().to_vec();
+            signature.push(recid.to_byte() + 27);
+            Operation::Move(self.account, x, y, self.seq, signature)
+        }
     }
 
     #[test]
     fn risc0_aca_works() {
-        let player1 = H160([1u8; 20]);
-        let player2 = H160([2u8; 20]);
-        let player3 = H160([3u8; 20]);
-        let player4 = H160([4u8; 20]);
+        let room = 100000;
+        let mut player1 = TestPlayer::new(1);
+        let mut player2 = TestPlayer::new(2);
+        let mut player3 = TestPlayer::new(3);
+        let mut player4 = TestPlayer::new(4);
 
         let operations = vec![
             Operation::CakeCreated(1, 1, 1),
             Operation::CakeMissed(1),
             Operation::CakeCreated(2, 2, 2),
-            Operation::Move(player1, 1, 1),
-            Operation::Move(player2, 1, 1),
-            Operation::Move(player3, 1, 1),
-            Operation::Move(player4, 1, 1),
-            Operation::Move(player1, 1, 2),
-            Operation::Move(player1, 2, 2),
-            Operation::Move(player4, 2, 1),
-            Operation::Move(player4, 2, 2),
+            player1.move_to(room, 1, 1),
+            player2.move_to(room, 1, 1),
+            player3.move_to(room, 1, 1),
+            player4.move_to(room, 1, 1),
+            player1.move_to(room, 1, 2),
+            player1.move_to(room, 2, 2),
+            player4.move_to(room, 2, 1),
+            player4.move_to(room, 2, 2),
+            Operation::CakeCreated(3, 3, 3),
+            player1.move_to(room, 2, 3),
+            player1.move_to(room, 3, 3),
+            Operation::CakeCreated(4, 4, 4),
+            player4.move_to(room, 2, 3),
+            player4.move_to(room, 2, 4),
+            player4.move_to(room, 3, 4),
+            player4.move_to(room, 4, 4),
+        ];
+
+        let all = [&player1, &player2, &player3, &player4];
+        let players: Vec<Address> = all.iter().map(|p| p.account).collect();
+        let peers: Vec<Address> = all.iter().map(|p| p.peer()).collect();
+        let seed = [7u8; 32];
+        let result = game_result(
+            room,
+            &players,
+            &peers,
+            &[2, 0, 0, 1],
+            operations_head(room, &seed, &operations),
+            seed,
+        );
+        let input = GameInput {
+            room,
+            players,
+            peers,
+            seed,
+            segments: vec![],
+            state: GameState::new(room, &seed, 4),
+            operations: Operations::Full(operations),
+        };
+        prove(&input, vec![], &result).unwrap();
+    }
+
+    #[test]
+    fn packed_operations_same_as_full() {
+        let room = 100000;
+        let mut player1 = TestPlayer::new(1);
+        let mut player2 = TestPlayer::new(2);
+
+        let operations = vec![
+            Operation::CakeCreated(2, 1, 1),
+            Operation::CakeCreated(1, 3, 3),
+            player1.move_to(room, 1, 1),
+            Operation::CakeCreated(3, 3, 3),
+            Operation::CakeMissed(1),
+            player2.move_to(room, 3, 3),
+            Operation::CakeCreated(4, 13, 20),
+            player1.move_to(room, 13, 20),
+            Operation::CakeCreated(5, 0, 0),
+        ];
+
+        let players = vec![player1.account, player2.account];
+        let peers = vec![player1.peer(), player2.peer()];
+        let seed = [7u8; 32];
+
+        let mut full = GameState::new(room, &seed, 2);
+        full.apply_all(room, &players, &peers, &Operations::Full(operations.clone()))
+            .unwrap();
+
+        let packed_ops = Operations::Packed(pack(&players, &operations).unwrap());
+        let mut packed = GameState::new(room, &seed, 2);
+        packed.apply_all(room, &players, &peers, &packed_ops).unwrap();
+
+        assert_eq!(full, packed);
+        assert_eq!(full.scores, vec![2, 1]);
+        assert_eq!(full.cakes, vec![(5, 0, 0)]);
+        assert_eq!(full.head, operations_head(room, &seed, &operations));
+    }
+
+    #[test]
+    fn risc0_aca_segments_works() {
+        let room = 100000;
+        let mut player1 = TestPlayer::new(1);
+        let mut player2 = TestPlayer::new(2);
+
+        let operations = vec![
+            Operation::CakeCreated(1, 1, 1),
+            player1.move_to(room, 1, 1),
+            Operation::CakeCreated(2, 2, 2),
+            player2.move_to(room, 2, 2),
             Operation::CakeCreated(3, 3, 3),
-            Operation::Move(player1, 2, 3),
-            Operation::Move(player1, 3, 3),
+            player1.move_to(room, 3, 3),
             Operation::CakeCreated(4, 4, 4),
-            Operation::Move(player4, 2, 3),
-            Operation::Move(player4, 2, 4),
-            Operation::Move(player4, 3, 4),
-            Operation::Move(player4, 4, 4),
         ];
 
-        let rank = simple_game_result(&[player1, player4]);
-        prove(&operations, &rank).unwrap();
+        let players = vec![player1.account, player2.account];
+        let peers = vec![player1.peer(), player2.peer()];
+        let seed = [7u8; 32];
+        let result = game_result(
+            room,
+            &players,
+            &peers,
+            &[2, 1],
+            operations_head(room, &seed, &operations),
+            seed,
+        );
+
+        let (inputs, state, rest) = split(room, &players, &peers, &seed, operations, 3).unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(rest.len(), 1);
+
+        let receipts: Vec<Receipt> = inputs.iter().map(|i| prove_segment(i).unwrap()).collect();
+        let segments = receipts.iter().map(|r| segment_journal(r).unwrap()).collect();
+        let input = GameInput {
+            room,
+            operations: Operations::Packed(pack(&players, &rest).unwrap()),
+            players,
+            peers,
+            seed,
+            segments,
+            state,
+        };
+        prove(&input, receipts, &result).unwrap();
+    }
+
+    #[test]
+    fn risc0_aca_rejects_forged_move() {
+        let room = 100000;
+        let mut player1 = TestPlayer::new(1);
+        let player2 = TestPlayer::new(2);
+
+        // sequencer signs a move for player2 with player1 key
+        let forged = match player1.move_to(room, 1, 1) {
+            Operation::Move(_, x, y, seq, signature) => {
+                Operation::Move(player2.account, x, y, seq, signature)
+            }
+            op => op,
+        };
+        let operations = vec![Operation::CakeCreated(1, 1, 1), forged];
+
+        let seed = [7u8; 32];
+        let input = GameInput {
+            room,
+            players: vec![player1.account, player2.account],
+            peers: vec![player1.peer(), player2.peer()],
+            seed,
+            segments: vec![],
+            state: GameState::new(room, &seed, 2),
+            operations: Operations::Full(operations),
+        };
+        // the guest panics at the forged move, not at the setup
+        let env = ExecutorEnv::builder().write(&input).unwrap().build().unwrap();
+        let Err(err) = default_executor().execute(env, ACA_ZK_ELF) else {
+            panic!("forged move executed");
+        };
+        assert!(err.to_string().contains("move not signed by player"), "{}", err);
+    }
+
+    #[test]
+    fn apply_rejects_missing_peers() {
+        let room = 100000;
+        let mut player1 = TestPlayer::new(1);
+        let player2 = TestPlayer::new(2);
+
+        let players = vec![player1.account, player2.account];
+        let operations = vec![player1.move_to(room, 1, 1)];
+        let seed = [7u8; 32];
+
+        let mut state = GameState::new(room, &seed, 2);
+        let full = Operations::Full(operations.clone());
+        assert!(state.apply_all(room, &players, &[player1.peer()], &full).is_err());
+        let packed = Operations::Packed(pack(&players, &operations).unwrap());
+        assert!(state.apply_all(room, &players, &[player1.peer()], &packed).is_err());
+
+        // the start state of other players
+        let mut state = GameState::new(room, &seed, 1);
+        let peers = [player1.peer(), player2.peer()];
+        assert!(state.apply_all(room, &players, &peers, &packed).is_err());
+    }
+
+    #[test]
+    fn game_result_ranks_scored_players() {
+        let player1 = H160([1u8; 20]);
+        let player2 = H160([2u8; 20]);
+        let player3 = H160([3u8; 20]);
+
+        let players = [player1, player2, player3];
+        let result = game_result(1, &players, &players, &[0, 3, 1], [0u8; 32], [0u8; 32]);
+        let expected = encode(&[
+            Token::Uint(1.into()),
+            Token::Array(vec![Token::Address(player1), Token::Address(player2), Token::Address(player3)]),
+            Token::Array(vec![Token::Address(player1), Token::Address(player2), Token::Address(player3)]),
+            Token::Array(vec![Token::Uint(0.into()), Token::Uint(3.into()), Token::Uint(1.into())]),
+            Token::Array(vec![Token::Address(player2), Token::Address(player3)]),
+            Token::FixedBytes(vec![0u8; 32]),
+            Token::FixedBytes(keccak256([0u8; 32]).to_vec()),
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn game_result_excludes_bots() {
+        let player1 = H160([1u8; 20]);
+        let bot = bot_account(0);
+
+        let result = game_result(1, &[player1, bot], &[player1, bot], &[1, 3], [0u8; 32], [0u8; 32]);
+        let expected = encode(&[
+            Token::Uint(1.into()),
+            Token::Array(vec![Token::Address(player1), Token::Address(bot)]),
+            Token::Array(vec![Token::Address(player1), Token::Address(bot)]),
+            Token::Array(vec![Token::Uint(1.into()), Token::Uint(3.into())]),
+            Token::Array(vec![Token::Address(player1)]),
+            Token::FixedBytes(vec![0u8; 32]),
+            Token::FixedBytes(keccak256([0u8; 32]).to_vec()),
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn verify_move_signed_by_peer_key() {
+        // the client signs with the z4 peer key and sends the signature as hex
+        let peer = z4_bevy::PeerKey::generate(&mut rand::thread_rng());
+        let message = move_message(1, 1, 2, 3);
+        let signature = hex::encode(peer.sign(&message).unwrap().to_bytes());
+
+        let signature = hex::decode(signature).unwrap();
+        let account = Address::from(peer.peer_id().0);
+        assert!(aca_core::verify_move(account, &message, &signature));
+        assert!(!aca_core::verify_move(account, &move_message(1, 2, 2, 3), &signature));
+        assert!(!aca_core::verify_move(H160([1u8; 20]), &message, &signature));
+    }
+
+    #[test]
+    fn operations_head_detects_rewrite() {
+        let mut player1 = TestPlayer::new(1);
+        let seed = [7u8; 32];
+
+        let operations = vec![
+            Operation::CakeCreated(1, 1, 1),
+            player1.move_to(1, 1, 1),
+        ];
+        let rewrite = vec![
+            Operation::CakeCreated(1, 1, 1),
+            player1.move_to(1, 1, 2),
+        ];
+        let head = operations_head(1, &seed, &operations);
+        assert_ne!(head, operations_head(1, &seed, &rewrite));
+        assert_ne!(head, operations_head(2, &seed, &operations));
+        assert_ne!(head, operations_head(1, &seed, &operations[..1]));
     }
 }
@@ -1,3 +1,10 @@
 fn main() {
+    // pin the segment image id in the game guest, the guest build inherits the env
+    let segment_id: Vec<String> = segment_methods::ACA_SEGMENT_ID
+        .iter()
+        .map(|word| word.to_string())
+        .collect();
+    std::env::set_var("SEGMENT_ID", segment_id.join(","));
+
     risc0_build::embed_methods();
 }
@@ -1,67 +1,48 @@
-use risc0_zkvm::guest::env;
-use ethers_core::{types::Address, abi::{encode, Token}};
-use serde::Deserialize;
-use std::collections::HashMap;
+use risc0_zkvm::{guest::env, sha::Digest};
+use aca_core::{game_result, players_hash, GameInput, GameState};
 
-#[derive(Deserialize)]
-pub enum Operation {
-    // player, position
-    Move(Address, usize, usize),
-    // index, position
-    CakeCreated(u32, usize, usize),
-    // index
-    CakeMissed(u32),
-}
-
-fn simple_game_result(ranks: &[Address]) -> Vec<u8> {
-    encode(&[Token::Array(
-        ranks.iter().map(|v| Token::Address(*v)).collect(),
-    )])
+/// the segment guest image id pinned by methods/build.rs, the prover cannot choose it
+fn segment_id() -> Digest {
+    let words: Vec<u32> = env!("SEGMENT_ID")
+        .split(',')
+        .map(|word| word.parse().expect("invalid segment id"))
+        .collect();
+    let words: [u32; 8] = words.try_into().expect("invalid segment id");
+    Digest::from(words)
 }
 
 fn main() {
     // read the input
-    let operations: Vec<Operation> = env::read();
+    let input: GameInput = env::read();
+    let players = players_hash(&input.players, &input.peers);
+    let segment_id = segment_id();
 
-    let mut cakes: HashMap<u32, (usize, usize)> = HashMap::new();
-    let mut scores: HashMap<Address, u32> = HashMap::new();
-    for op in operations {
-        match op {
-            Operation::Move(player, x, y) => {
-                let mut clears: Vec<u32> = vec![];
-                for (i, (x1, y1)) in cakes.iter() {
-                    if *x1 == x && *y1 == y {
-                        clears.push(*i)
-                    }
-                }
+    // the proven segments must be chained from the initial state
+    let mut start = GameState::new(input.room, &input.seed, input.players.len()).commitment();
+    for journal in &input.segments {
+        assert_eq!(journal.room, input.room, "segment of other room");
+        assert_eq!(journal.players, players, "segment of other players");
+        assert_eq!(journal.start, start, "segment not chained");
 
-                loop {
-                    if let Some(next) = clears.pop() {
-                        cakes.remove(&next);
-                        scores.entry(player).and_modify(|s| {
-                            *s += 1
-                        }).or_insert(1);
-                    } else {
-                        break;
-                    }
-                }
-            }
-            Operation::CakeCreated(index, x, y) => {
-                cakes.insert(index, (x, y));
-            }
-            Operation::CakeMissed(index) => {
-                let _ = cakes.remove(&index);
-            }
-        }
+        env::verify(segment_id, &journal.to_bytes()).expect("invalid segment");
+        start = journal.end;
     }
+    assert_eq!(input.state.commitment(), start, "state not match segments");
+
+    // the rest operations after segments
+    let mut state = input.state;
+    state
+        .apply_all(input.room, &input.players, &input.peers, &input.operations)
+        .expect("invalid operation");
 
     // write public output to the journal
-    let mut players: Vec<(Address, u32)> = scores
-        .iter()
-        .filter_map(|(account, score)| if *score > 0 { Some((*account, *score)) } else { None })
-        .collect();
-    players.sort_by(|(_, sa), (_, sb)| sb.cmp(sa));
-    let winners: Vec<Address> = players.iter().map(|(a, _s)| *a).collect();
-    let rank = simple_game_result(&winners);
-    env::commit(&rank);
+    let result = game_result(
+        input.room,
+        &input.players,
+        &input.peers,
+        &state.scores,
+        state.head,
+        input.seed,
+    );
+    env::commit(&result);
 }
@@ -0,0 +1,21 @@
+use risc0_zkvm::guest::env;
+use aca_core::{players_hash, SegmentInput, SegmentJournal};
+
+fn main() {
+    // read the input
+    let input: SegmentInput = env::read();
+
+    let mut state = input.start.clone();
+    state
+        .apply_all(input.room, &input.players, &input.peers, &input.operations)
+        .expect("invalid operation");
+
+    // write start and end state to the journal
+    let journal = SegmentJournal {
+        room: input.room,
+        players: players_hash(&input.players, &input.peers),
+        start: input.start.commitment(),
+        end: state.commitment(),
+    };
+    env::commit_slice(&journal.to_bytes());
+}